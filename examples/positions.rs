@@ -3,9 +3,9 @@ use odt::Arena;
 use odt::error::Scribe;
 use odt::fs::{Loader, LocalFileLoader};
 use odt::line::LineTableCache;
-use odt::merge::{NodeChange, PropChange, merge};
+use odt::merge::merge;
 use odt::parse::parse_with_includes;
-use pest::Span;
+use odt::provenance::{node_history, property_history, source_location};
 use std::path::PathBuf;
 
 #[derive(clap::Parser)]
@@ -45,39 +45,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let ltc = LineTableCache::default();
 
-    fn source_location<'a>(
-        loader: &'a LocalFileLoader,
-        ltc: &LineTableCache<'a>,
-        span: &Span<'a>,
-    ) -> (PathBuf, usize, usize) {
-        let buffer = span.get_input().as_bytes().as_ptr_range();
-        let path = loader.path_of_buffer(buffer).unwrap();
-        let (line, col) = ltc.start_line_col(span);
-        // The column value is in codepoints, not bytes.
-        (path, line, col)
-    }
-
     // show locations of all nodes
     for (path, history) in node_changes {
         println!("history of node {path}:");
-        let mut exists = false;
-        for change in history {
-            let verb = match change {
-                NodeChange::TopDelNode(_) | NodeChange::DelNode(_) => {
-                    exists = false;
-                    "deleted"
-                }
-                _ => {
-                    if exists {
-                        "updated"
-                    } else {
-                        exists = true;
-                        "created"
-                    }
-                }
-            };
-            let (file, line, col) = source_location(&loader, &ltc, change.span());
-            println!("  {verb} at {}:{line}:{col}", file.display());
+        for event in node_history(&history) {
+            let (file, line, col) = source_location(&loader, &ltc, &event.span);
+            println!("  {} at {}:{line}:{col}", event.action, file.display());
         }
         println!();
     }
@@ -85,24 +58,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // show locations of all properties
     for (path, history) in prop_changes {
         println!("history of property {path}:");
-        let mut exists = false;
-        for change in history {
-            let verb = match change {
-                PropChange::DelProp(_) | PropChange::TopDelNode(_) | PropChange::DelNode(_) => {
-                    exists = false;
-                    "deleted"
-                }
-                _ => {
-                    if exists {
-                        "updated"
-                    } else {
-                        exists = true;
-                        "created"
-                    }
-                }
-            };
-            let (file, line, col) = source_location(&loader, &ltc, change.span());
-            println!("  {verb} at {}:{line}:{col}", file.display());
+        for event in property_history(&history) {
+            let (file, line, col) = source_location(&loader, &ltc, &event.span);
+            println!("  {} at {}:{line}:{col}", event.action, file.display());
         }
         println!();
     }
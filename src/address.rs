@@ -0,0 +1,478 @@
+//! Address translation:  from the bus addresses of a node's `reg` to CPU physical addresses,
+//! through the `ranges` of each bus between the node and the root, and likewise from DMA
+//! addresses through `dma-ranges`.
+//!
+//! A bus with an empty `ranges` maps its children's addresses unchanged into its parent's
+//! address space; a bus without one cannot be translated through, as with I2C or `/cpus`.
+//! Addresses of up to four cells are supported.  They are compared as plain numbers, so the
+//! flags in the first cell of a PCI address must match those of the `ranges` entry exactly.
+
+use crate::BinaryNode;
+use crate::path::NodePath;
+use core::fmt::{Display, Formatter};
+
+/// A range of addresses.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Region {
+    pub address: u128,
+    pub size: u128,
+}
+
+/// A window through which devices on a bus reach CPU physical addresses.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DmaRange {
+    /// The first address of the window, as seen by devices on the bus.
+    pub bus_address: u128,
+    /// The CPU physical address of `bus_address`.
+    pub cpu_address: u128,
+    pub size: u128,
+}
+
+/// Which property maps a bus's addresses into its parent's address space.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mapping {
+    /// `ranges`, for addresses which the CPU accesses, as in `reg`.
+    Ranges,
+    /// `dma-ranges`, for addresses which devices on the bus access.
+    DmaRanges,
+}
+
+impl Mapping {
+    pub fn property(self) -> &'static str {
+        match self {
+            Mapping::Ranges => "ranges",
+            Mapping::DmaRanges => "dma-ranges",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AddressError {
+    NoNode(NodePath),
+    /// The root node has no parent bus to be addressed on.
+    Root,
+    NoReg(NodePath),
+    /// `#address-cells` or `#size-cells` is larger than supported.
+    UnsupportedCells {
+        path: NodePath,
+        property: &'static str,
+        cells: u32,
+    },
+    /// The length of a property is not a multiple of its entries' length.
+    Malformed {
+        path: NodePath,
+        property: &'static str,
+        len: usize,
+        entry_cells: u32,
+    },
+    /// A bus has no `ranges` (or `dma-ranges`), so addresses on it do not map to its parent.
+    Untranslatable {
+        bus: NodePath,
+        mapping: Mapping,
+    },
+    /// An address lies outside every entry of a bus's `ranges`.
+    Unmapped {
+        bus: NodePath,
+        mapping: Mapping,
+        address: u128,
+    },
+    /// A region begins inside an entry of a bus's `ranges` but extends beyond it.
+    Crosses {
+        bus: NodePath,
+        mapping: Mapping,
+        region: Region,
+    },
+}
+
+impl Display for AddressError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AddressError::NoNode(path) => write!(f, "no node {path}"),
+            AddressError::Root => write!(f, "the root node has no address"),
+            AddressError::NoReg(path) => write!(f, "{path} has no reg property"),
+            AddressError::UnsupportedCells {
+                path,
+                property,
+                cells,
+            } => write!(
+                f,
+                "{path} has {property} = <{cells}>; at most 4 are supported"
+            ),
+            AddressError::Malformed {
+                path,
+                property,
+                len,
+                entry_cells,
+            } => write!(
+                f,
+                "{path}/{property} has {len} bytes, which is not a multiple of {entry_cells} cells"
+            ),
+            AddressError::Untranslatable { bus, mapping } => {
+                let property = mapping.property();
+                write!(
+                    f,
+                    "{bus} has no {property} property, so its addresses are not translatable"
+                )
+            }
+            AddressError::Unmapped {
+                bus,
+                mapping,
+                address,
+            } => {
+                let property = mapping.property();
+                write!(
+                    f,
+                    "address {address:#x} is outside every entry of {bus}/{property}"
+                )
+            }
+            AddressError::Crosses {
+                bus,
+                mapping,
+                region,
+            } => {
+                let property = mapping.property();
+                let Region { address, size } = region;
+                write!(
+                    f,
+                    "region {address:#x} (size {size:#x}) extends beyond its entry of {bus}/{property}"
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for AddressError {}
+
+fn walk<'t>(tree: &'t BinaryNode, path: &NodePath) -> Result<&'t BinaryNode, AddressError> {
+    tree.walk(path.segments())
+        .ok_or_else(|| AddressError::NoNode(path.clone()))
+}
+
+/// The `#address-cells` or `#size-cells` of the node at `path`, or its default.
+fn cells(node: &BinaryNode, path: &NodePath, property: &'static str) -> Result<u32, AddressError> {
+    let cells = match property {
+        "#address-cells" => node.address_cells(),
+        _ => node.size_cells(),
+    };
+    match cells {
+        0..=4 => Ok(cells),
+        _ => Err(AddressError::UnsupportedCells {
+            path: path.clone(),
+            property,
+            cells,
+        }),
+    }
+}
+
+/// Split the property `property` of the node at `path` into entries of numbers, each number
+/// being of the given number of cells.
+fn entries(
+    path: &NodePath,
+    value: &[u8],
+    property: &'static str,
+    widths: &[u32],
+) -> Result<Vec<Vec<u128>>, AddressError> {
+    let entry_cells = widths.iter().sum::<u32>();
+    let entry_len = 4 * entry_cells as usize;
+    if entry_len == 0 || !value.len().is_multiple_of(entry_len) {
+        return Err(AddressError::Malformed {
+            path: path.clone(),
+            property,
+            len: value.len(),
+            entry_cells,
+        });
+    }
+    let entries = value.chunks(entry_len).map(|mut entry| {
+        let mut numbers = vec![];
+        for &width in widths {
+            let (number, rest) = entry.split_at(4 * width as usize);
+            numbers.push(number.iter().fold(0, |n, &b| (n << 8) | b as u128));
+            entry = rest;
+        }
+        numbers
+    });
+    Ok(entries.collect())
+}
+
+/// Translate `region`, an address range on the bus at `bus`, into the root's address space
+/// through each bus's `ranges` or `dma-ranges`.
+pub fn translate(
+    tree: &BinaryNode,
+    bus: &NodePath,
+    mut region: Region,
+    mapping: Mapping,
+) -> Result<Region, AddressError> {
+    let property = mapping.property();
+    let mut bus = bus.clone();
+    while !bus.is_root() {
+        let node = walk(tree, &bus)?;
+        let Some(value) = node.get_property(property) else {
+            return Err(AddressError::Untranslatable { bus, mapping });
+        };
+        let parent_path = bus.parent();
+        if !value.is_empty() {
+            let parent = walk(tree, &parent_path)?;
+            let widths = [
+                cells(node, &bus, "#address-cells")?,
+                cells(parent, &parent_path, "#address-cells")?,
+                cells(node, &bus, "#size-cells")?,
+            ];
+            let entries = entries(&bus, value, property, &widths)?;
+            let Some(entry) = entries
+                .iter()
+                .find(|e| e[0] <= region.address && region.address - e[0] < e[2])
+            else {
+                let address = region.address;
+                return Err(AddressError::Unmapped {
+                    bus,
+                    mapping,
+                    address,
+                });
+            };
+            let offset = region.address - entry[0];
+            if offset
+                .checked_add(region.size)
+                .is_none_or(|end| end > entry[2])
+            {
+                return Err(AddressError::Crosses {
+                    bus,
+                    mapping,
+                    region,
+                });
+            }
+            region.address = entry[1].wrapping_add(offset);
+        }
+        bus = parent_path;
+    }
+    Ok(region)
+}
+
+/// The CPU physical address ranges of the node at `path`, given by its `reg`.
+pub fn reg(tree: &BinaryNode, path: &NodePath) -> Result<Vec<Region>, AddressError> {
+    if path.is_root() {
+        return Err(AddressError::Root);
+    }
+    let node = walk(tree, path)?;
+    let bus = path.parent();
+    let parent = walk(tree, &bus)?;
+    let Some(value) = node.get_property("reg") else {
+        return Err(AddressError::NoReg(path.clone()));
+    };
+    let widths = [
+        cells(parent, &bus, "#address-cells")?,
+        cells(parent, &bus, "#size-cells")?,
+    ];
+    entries(path, value, "reg", &widths)?
+        .into_iter()
+        .map(|e| {
+            let region = Region {
+                address: e[0],
+                size: e[1],
+            };
+            translate(tree, &bus, region, Mapping::Ranges)
+        })
+        .collect()
+}
+
+/// The windows through which devices on the bus at `bus` reach CPU physical addresses, given
+/// by its `dma-ranges` and those of its ancestors.  A bus with an empty `dma-ranges` has the
+/// windows of its parent, and the root has one window spanning its address space.
+pub fn dma_ranges(tree: &BinaryNode, bus: &NodePath) -> Result<Vec<DmaRange>, AddressError> {
+    let node = walk(tree, bus)?;
+    if bus.is_root() {
+        let bits = 32 * cells(node, bus, "#address-cells")?;
+        let size = 1u128.checked_shl(bits).unwrap_or(u128::MAX);
+        return Ok(vec![DmaRange {
+            bus_address: 0,
+            cpu_address: 0,
+            size,
+        }]);
+    }
+    let mapping = Mapping::DmaRanges;
+    let property = mapping.property();
+    let Some(value) = node.get_property(property) else {
+        let bus = bus.clone();
+        return Err(AddressError::Untranslatable { bus, mapping });
+    };
+    let parent_path = bus.parent();
+    if value.is_empty() {
+        return dma_ranges(tree, &parent_path);
+    }
+    let parent = walk(tree, &parent_path)?;
+    let widths = [
+        cells(node, bus, "#address-cells")?,
+        cells(parent, &parent_path, "#address-cells")?,
+        cells(node, bus, "#size-cells")?,
+    ];
+    entries(bus, value, property, &widths)?
+        .into_iter()
+        .map(|e| {
+            let region = Region {
+                address: e[1],
+                size: e[2],
+            };
+            let cpu = translate(tree, &parent_path, region, mapping)?;
+            Ok(DmaRange {
+                bus_address: e[0],
+                cpu_address: cpu.address,
+                size: e[2],
+            })
+        })
+        .collect()
+}
+
+/// One region of the memory map.
+pub struct MapEntry {
+    pub path: NodePath,
+    pub region: Region,
+    /// The node's first `compatible` string, if any.
+    pub compatible: Option<String>,
+    /// The node's `status`, if any.
+    pub status: Option<String>,
+    /// Whether the node and all its ancestors are enabled, lacking `status` or having
+    /// `status = "okay"`.
+    pub enabled: bool,
+}
+
+/// Every translatable `reg` region in the tree, sorted by CPU physical address.  Nodes on buses
+/// without `ranges`, such as I2C devices, are omitted.
+pub fn memory_map(tree: &BinaryNode) -> Vec<MapEntry> {
+    let mut entries = vec![];
+    let mut disabled: Vec<NodePath> = vec![];
+    tree.for_each_node(&NodePath::root(), &mut |path, node| {
+        let status = node.get_string("status");
+        if !matches!(status, None | Some("okay" | "ok")) {
+            disabled.push(path.clone());
+        }
+        if path.is_root() || node.get_property("reg").is_none() {
+            return;
+        }
+        let Ok(regions) = reg(tree, path) else {
+            return;
+        };
+        let enabled = !disabled.iter().any(|d| path.starts_with(d));
+        for region in regions {
+            entries.push(MapEntry {
+                path: path.clone(),
+                region,
+                compatible: node.get_strings("compatible").map(|c| c[0].to_string()),
+                status: status.map(str::to_string),
+                enabled,
+            });
+        }
+    });
+    entries.sort_by_key(|e| (e.region.address, e.region.size));
+    entries
+}
+
+/// Describe the memory map, one region per line:  address, size, path, compatible, and status.
+pub fn format_memory_map(tree: &BinaryNode) -> String {
+    use core::fmt::Write;
+    let mut out = String::new();
+    for e in memory_map(tree) {
+        let Region { address, size } = e.region;
+        let path = &e.path;
+        let compatible = e.compatible.as_deref().unwrap_or("-");
+        let status = match (&e.status, e.enabled) {
+            (Some(status), _) => status,
+            (None, true) => "okay",
+            (None, false) => "(disabled parent)",
+        };
+        _ = writeln!(
+            out,
+            "{address:#018x} {size:#010x} {path} {compatible} {status}"
+        );
+    }
+    out
+}
+
+#[test]
+fn test_address() {
+    let source = r#"
+/dts-v1/;
+/ {
+    #address-cells = <2>;
+    #size-cells = <2>;
+    soc {
+        #address-cells = <1>;
+        #size-cells = <1>;
+        ranges = <0x0 0x0 0x40000000 0x10000000>;
+        dma-ranges = <0x80000000 0x0 0x0 0x40000000>;
+        uart@1000 { reg = <0x1000 0x100>, <0x2000 0x100>; };
+        bus@8000000 {
+            #address-cells = <1>;
+            #size-cells = <1>;
+            ranges;
+            dma-ranges;
+            dev@8001000 { reg = <0x8001000 0x1000>; };
+            late@ffff000 { reg = <0xffff000 0x2000>; };
+            far@20000000 { reg = <0x20000000 0x10>; };
+        };
+        i2c@3000 {
+            reg = <0x3000 0x100>;
+            #address-cells = <1>;
+            #size-cells = <0>;
+            codec@1a { reg = <0x1a>; };
+        };
+    };
+};
+"#;
+    let arena = crate::Arena::new();
+    let dts = crate::parse::parse_typed(source, &arena).unwrap();
+    let mut scribe = crate::error::Scribe::new(true);
+    let (tree, labels, _, _) = crate::merge::merge(dts, &mut scribe);
+    let tree = crate::eval::eval(tree, labels, &crate::fs::DummyLoader, &mut scribe);
+    assert!(scribe.report(&crate::fs::DummyLoader, &mut std::io::stderr()));
+    let reg = |path: &str| reg(&tree, &NodePath::root().join(path));
+    let region = |address, size| Region { address, size };
+    assert_eq!(
+        reg("soc/uart@1000"),
+        Ok(vec![region(0x40001000, 0x100), region(0x40002000, 0x100)])
+    );
+    assert_eq!(
+        reg("soc/bus@8000000/dev@8001000"),
+        Ok(vec![region(0x48001000, 0x1000)])
+    );
+    let soc = NodePath::root().join("soc");
+    assert_eq!(
+        reg("soc/bus@8000000/late@ffff000").unwrap_err().to_string(),
+        "region 0xffff000 (size 0x2000) extends beyond its entry of /soc/ranges"
+    );
+    assert_eq!(
+        reg("soc/bus@8000000/far@20000000"),
+        Err(AddressError::Unmapped {
+            bus: soc.clone(),
+            mapping: Mapping::Ranges,
+            address: 0x20000000,
+        })
+    );
+    assert_eq!(
+        reg("soc/i2c@3000/codec@1a").unwrap_err().to_string(),
+        "/soc/i2c@3000 has no ranges property, so its addresses are not translatable"
+    );
+    assert_eq!(reg("soc"), Err(AddressError::NoReg(soc.clone())));
+    let window = DmaRange {
+        bus_address: 0x80000000,
+        cpu_address: 0,
+        size: 0x40000000,
+    };
+    assert_eq!(
+        dma_ranges(&tree, &soc.join("bus@8000000")),
+        Ok(vec![window])
+    );
+    let dma = Region {
+        address: 0x80001000,
+        size: 0x1000,
+    };
+    assert_eq!(
+        translate(&tree, &soc, dma, Mapping::DmaRanges),
+        Ok(region(0x1000, 0x1000))
+    );
+    assert_eq!(
+        format_memory_map(&tree),
+        "0x0000000040001000 0x00000100 /soc/uart@1000 - okay\n\
+         0x0000000040002000 0x00000100 /soc/uart@1000 - okay\n\
+         0x0000000040003000 0x00000100 /soc/i2c@3000 - okay\n\
+         0x0000000048001000 0x00001000 /soc/bus@8000000/dev@8001000 - okay\n"
+    );
+}
@@ -0,0 +1,233 @@
+use clap::Parser as _;
+use odt::error::Scribe;
+use odt::fs::{Loader, LocalFileLoader, TrackingLoader};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Compile many DTS targets in one process, sharing the contents of common includes.
+#[derive(clap::Parser)]
+#[command(version, args_override_self = true)]
+struct Args {
+    /// Input files, compiled into the output directory
+    #[arg(value_name = "input_path")]
+    input_path: Vec<PathBuf>,
+
+    /// File listing further targets, one per line:  an input path and an output path,
+    /// separated by whitespace.  Blank lines and lines starting with '#' are ignored.
+    #[arg(short = 'm', long, value_name = "path")]
+    manifest: Option<PathBuf>,
+
+    /// Output directory for input files named on the command line
+    #[arg(short = 'o', long, value_name = "path", default_value = ".")]
+    out_dir: PathBuf,
+
+    /// Output format
+    #[arg(short = 'O', long, value_name = "format", default_value = "dtb")]
+    out_format: Format,
+
+    /// Write a dependency file for each target, named by appending ".d" to its output path
+    #[arg(short = 'd', long)]
+    depfiles: bool,
+
+    /// Add a directory to the include search path
+    #[arg(short = 'i', long, value_name = "path")]
+    include: Vec<PathBuf>,
+
+    /// Number of threads (default: available parallelism)
+    #[arg(short = 'j', long, value_name = "n")]
+    jobs: Option<usize>,
+
+    /// Sort output trees alphabetically.
+    #[arg(short = 's', long)]
+    sort: bool,
+
+    /// Validate nodes against the devicetree bindings (dt-schema YAML files) in this directory,
+    /// as the check `schema`.
+    #[cfg(feature = "schema")]
+    #[arg(long, value_name = "dir")]
+    schema: Option<PathBuf>,
+
+    /// Enable a check, or disable it with `no-<check>`.  (Applied before any -E.)
+    #[arg(short = 'W', value_name = "check")]
+    warning: Vec<String>,
+
+    /// Make a check's findings errors, or demote them to warnings with `no-<check>`.
+    #[arg(short = 'E', value_name = "check")]
+    error: Vec<String>,
+
+    /// Treat all warnings as errors.
+    #[arg(long)]
+    treat_warnings_as_errors: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Format {
+    /// devicetree blob
+    Dtb,
+    /// fully-evaluated devicetree source
+    Dtv,
+}
+
+struct Target {
+    input: PathBuf,
+    output: PathBuf,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let targets = targets(&args)?;
+    let checks = checks(&args)?;
+    let jobs = args
+        .jobs
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, targets.len().max(1));
+
+    let files = LocalFileLoader::new(args.include.clone());
+    let next = AtomicUsize::new(0);
+    let failures = Mutex::new(vec![]);
+    std::thread::scope(|s| {
+        for _ in 0..jobs {
+            s.spawn(|| worker(&args, &checks, &files, &targets, &next, &failures));
+        }
+    });
+
+    let mut failures = failures.into_inner().unwrap();
+    failures.sort_unstable();
+    let n = targets.len();
+    eprintln!("compiled {n} targets, {} failed", failures.len());
+    for (input, reason) in &failures {
+        eprintln!("  {}: {reason}", input.display());
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        std::process::exit(1)
+    }
+}
+
+/// The checks to run, configured by -W and -E.
+fn checks(args: &Args) -> Result<odt::checks::Registry, String> {
+    #[cfg(feature = "schema")]
+    let schema = args.schema.as_deref();
+    #[cfg(not(feature = "schema"))]
+    let schema = None;
+    odt::checks::Registry::from_flags(schema, &args.warning, &args.error)
+}
+
+/// Gather the targets named on the command line and in the manifest.
+fn targets(args: &Args) -> Result<Vec<Target>, Box<dyn std::error::Error>> {
+    let mut targets = vec![];
+    let extension = match args.out_format {
+        Format::Dtb => "dtb",
+        Format::Dtv => "dts",
+    };
+    for input in &args.input_path {
+        let Some(stem) = input.file_stem() else {
+            return Err(format!("no file name in {input:?}").into());
+        };
+        let output = args.out_dir.join(stem).with_extension(extension);
+        targets.push(Target {
+            input: input.clone(),
+            output,
+        });
+    }
+    if let Some(manifest) = &args.manifest {
+        let text = std::fs::read_to_string(manifest)?;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [input, output] = fields[..] else {
+                let lineno = i + 1;
+                return Err(
+                    format!("{manifest:?}:{lineno}: expected input and output paths").into(),
+                );
+            };
+            targets.push(Target {
+                input: input.into(),
+                output: output.into(),
+            });
+        }
+    }
+    let mut outputs = std::collections::HashSet::new();
+    for target in &targets {
+        if !outputs.insert(&target.output) {
+            return Err(format!("more than one target writes {:?}", target.output).into());
+        }
+    }
+    Ok(targets)
+}
+
+/// Compile targets until none remain.  Each thread keeps its own arena and parsed files, while
+/// file contents are shared by all threads.
+fn worker(
+    args: &Args,
+    checks: &odt::checks::Registry,
+    files: &LocalFileLoader,
+    targets: &[Target],
+    next: &AtomicUsize,
+    failures: &Mutex<Vec<(PathBuf, String)>>,
+) {
+    let loader = TrackingLoader::new(files);
+    let arena = odt::Arena::new();
+    let mut parse_cache = odt::parse::ParseCache::default();
+    while let Some(target) = targets.get(next.fetch_add(1, Ordering::Relaxed)) {
+        let mut scribe = Scribe::new(args.treat_warnings_as_errors);
+        let paths = &[target.input.as_path()];
+        let mut tree = odt::compile_cached(
+            &loader,
+            &arena,
+            paths,
+            &mut parse_cache,
+            None,
+            checks,
+            &mut scribe,
+        );
+        // Buffer diagnostics so that those of different targets are not interleaved.
+        let mut log = vec![];
+        let ok = scribe.report(&loader, &mut log);
+        std::io::stderr().write_all(&log).unwrap();
+        let result = if ok {
+            if args.sort {
+                tree.sort();
+            }
+            write_outputs(args, target, &tree, &loader)
+        } else {
+            Err("compilation failed".into())
+        };
+        if let Err(e) = result {
+            failures
+                .lock()
+                .unwrap()
+                .push((target.input.clone(), e.to_string()));
+        }
+    }
+}
+
+fn write_outputs(
+    args: &Args,
+    target: &Target,
+    tree: &odt::BinaryNode,
+    loader: &impl Loader,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bytes = match args.out_format {
+        Format::Dtb => odt::flat::serialize(tree),
+        Format::Dtv => odt::print::format_binary(tree).into_bytes(),
+    };
+    if let Some(dir) = target.output.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    odt::fs::write_atomic(&target.output, &bytes)?;
+    if args.depfiles {
+        let goal = target.output.to_string_lossy();
+        let mut depfile = target.output.clone().into_os_string();
+        depfile.push(".d");
+        let content = loader.write_depfile(&goal);
+        odt::fs::write_atomic(Path::new(&depfile), content.as_bytes())?;
+    }
+    Ok(())
+}
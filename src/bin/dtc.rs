@@ -1,6 +1,7 @@
 use clap::Parser as _;
 use odt::parse::TypedRuleExt;
 use odt::parse::rules::TopDef;
+use odt::query::{Query, Selector};
 use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 
@@ -31,11 +32,67 @@ struct Args {
     #[arg(short = 'i', long, value_name = "path")]
     include: Vec<PathBuf>,
 
+    /// Instead of compiling, list each place the node or property at this path was created,
+    /// updated, or deleted.  The path may begin with a label, as in `&uart0/status`.
+    #[arg(long, value_name = "path")]
+    history: Vec<String>,
+
+    /// Instead of compiling, list each definition which has no effect on the merged tree, because
+    /// it is overridden or deleted later, grouped by file.
+    #[arg(long)]
+    shadowed: bool,
+
+    /// Instead of compiling, rewrite the source files to apply the fixes suggested by checks,
+    /// such as correcting unit addresses.  Other text is left as it was.
+    #[arg(long)]
+    fix: bool,
+
+    /// Reuse the compiled tree recorded in this file if no input has changed, and record it
+    /// otherwise.
+    #[arg(long, value_name = "path")]
+    cache: Option<PathBuf>,
+
+    /// Instead of the compiled tree, output each endpoint of the OF graph (the `port` and
+    /// `endpoint` nodes linking devices) and the endpoint it is connected to.
+    #[arg(long)]
+    graph: bool,
+
+    /// Instead of the compiled tree, output each `reg` region translated to a CPU physical
+    /// address, sorted by address, with its node's path, compatible, and status.
+    #[arg(long)]
+    memory_map: bool,
+
+    /// Output only the nodes matching this selector, with their properties and the ancestors
+    /// needed to reach them, as in `--select '/soc/**[compatible*="arm,pl011"]'`.  See
+    /// `odt::query` for the syntax.
+    #[arg(long, value_name = "selector")]
+    select: Option<String>,
+
+    /// Annotate DTS output with the source location of each node and property.
+    #[arg(short = 'T', long)]
+    annotate: bool,
+
     /// Sort output tree alphabetically.
     #[arg(short = 's', long)]
     sort: bool,
 
-    #[arg(short = 'W', long)]
+    /// Validate nodes against the devicetree bindings (dt-schema YAML files) in this directory,
+    /// as the check `schema`.
+    #[cfg(feature = "schema")]
+    #[arg(long, value_name = "dir")]
+    schema: Option<PathBuf>,
+
+    /// Enable a check, or disable it with `no-<check>`.  (Applied before any -E.)  This used to
+    /// be short for --treat-warnings-as-errors, which must now be spelled out.
+    #[arg(short = 'W', value_name = "check")]
+    warning: Vec<String>,
+
+    /// Make a check's findings errors, or demote them to warnings with `no-<check>`.
+    #[arg(short = 'E', value_name = "check")]
+    error: Vec<String>,
+
+    /// Treat all warnings as errors.
+    #[arg(long)]
     treat_warnings_as_errors: bool,
 }
 
@@ -62,27 +119,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 fn dtb_input(args: Args) -> Result<(), Box<dyn std::error::Error>> {
     use odt::fs::{Loader, LocalFileLoader};
-    let loader = LocalFileLoader::new(args.include);
-    let input = args.input_path.unwrap_or(LocalFileLoader::STDIN.into());
+    let checks = checks(&args)?;
+    let loader = LocalFileLoader::new(args.include.clone());
+    let input = args.input_path.clone();
+    let input = input.unwrap_or(LocalFileLoader::STDIN.into());
     let Some((_path, data)) = loader.read(input.clone()) else {
         panic!("can't read {input:?}");
     };
     let mut tree = odt::flat::deserialize(data)?;
+    let mut scribe = odt::error::Scribe::new(args.treat_warnings_as_errors);
+    checks.run(&odt::checks::Context::new(&tree, None), &mut scribe);
+    if !scribe.report(&loader, &mut std::io::stderr()) {
+        return Err("checks failed".into());
+    }
+    if let Some(selector) = selector(&args)? {
+        tree = select_binary(&tree, &selector);
+    }
     if args.sort {
         tree.sort();
     }
     let (goal, mut writer) = open_output(args.out)?;
     match args.out_format {
+        _ if args.graph => write!(writer, "{}", odt::graph::format(&tree))?,
+        _ if args.memory_map => write!(writer, "{}", odt::address::format_memory_map(&tree))?,
         Format::Dtb => {
             let dtb = odt::flat::serialize(&tree);
             writer.write_all(&dtb)?;
         }
         Format::Dti | Format::Dts | Format::Dtv => {
-            let source = format!("/dts-v1/;/{tree};");
-            // Reparse and pretty-print the output.
-            let tree = odt::parse::parse_untyped(&source).unwrap();
-            let output = odt::print::format(tree);
-            write!(writer, "{output}")?;
+            write!(writer, "{}", odt::print::format_binary(&tree))?;
         }
     }
     if let Some(depfile) = args.out_dependency {
@@ -93,14 +158,57 @@ fn dtb_input(args: Args) -> Result<(), Box<dyn std::error::Error>> {
 }
 
 fn dts_input(args: Args) -> Result<(), Box<dyn std::error::Error>> {
-    use odt::fs::{Loader, LocalFileLoader};
-    let loader = LocalFileLoader::new(args.include);
+    use odt::cache::BuildCache;
+    use odt::fs::{Loader, LocalFileLoader, TrackingLoader};
+    let checks = checks(&args)?;
+    let selector = selector(&args)?;
+    let files = LocalFileLoader::new(args.include.clone());
+    let loader = TrackingLoader::new(&files);
     let input = args.input_path.unwrap_or(LocalFileLoader::STDIN.into());
     let arena = odt::Arena::new();
     let mut scribe = odt::error::Scribe::new(args.treat_warnings_as_errors);
+    if !args.history.is_empty() {
+        return print_history(&args.history, &loader, &arena, &input, scribe);
+    }
+    if args.shadowed {
+        return print_shadowed(&loader, &arena, &input, scribe);
+    }
+    if args.fix {
+        return fix_sources(&checks, &loader, args.include, &arena, &input, scribe);
+    }
+    let mut build_cache = match &args.cache {
+        Some(path) if path.exists() => Some(BuildCache::load(path)?),
+        Some(_) => Some(BuildCache::new()),
+        None => None,
+    };
+    let mut compile = |scribe: &mut odt::error::Scribe| {
+        let mut parse_cache = Default::default();
+        let paths = &[input.as_path()];
+        let build_cache = build_cache.as_mut();
+        odt::compile_cached(
+            &loader,
+            &arena,
+            paths,
+            &mut parse_cache,
+            build_cache,
+            &checks,
+            scribe,
+        )
+    };
     let bytes = match args.out_format {
+        _ if args.graph => odt::graph::format(&compile(&mut scribe)).into_bytes(),
+        _ if args.memory_map => odt::address::format_memory_map(&compile(&mut scribe)).into_bytes(),
+        Format::Dti | Format::Dts | Format::Dtv if args.annotate && selector.is_some() => {
+            return Err("--select cannot be used with --annotate".into());
+        }
+        Format::Dti if selector.is_some() => {
+            return Err("--select cannot be used with -O dti".into());
+        }
         Format::Dtb => {
-            let mut tree = odt::compile(&loader, &arena, &[&input], &mut scribe);
+            let mut tree = compile(&mut scribe);
+            if let Some(selector) = &selector {
+                tree = select_binary(&tree, selector);
+            }
             if args.sort {
                 tree.sort();
             }
@@ -119,10 +227,24 @@ fn dts_input(args: Args) -> Result<(), Box<dyn std::error::Error>> {
             }
             output.into_bytes()
         }
+        Format::Dts | Format::Dtv if args.annotate => {
+            let evaluate = args.out_format == Format::Dtv;
+            let source =
+                annotated_source(&loader, &arena, &input, evaluate, args.sort, &mut scribe);
+            // Reparse and pretty-print the output.
+            let tree = odt::parse::parse_untyped(&source).unwrap();
+            let output = odt::print::format(tree);
+            output.into_bytes()
+        }
         Format::Dts => {
             // This shows the tree after /include/ directives and merge operations,
             // but before assigning phandles or evaluating expressions.
             let mut tree = odt::merge(&loader, &arena, &[&input], &mut scribe);
+            if let Some(selector) = &selector {
+                let selected = Query::source(&tree).select(selector);
+                let paths: Vec<_> = selected.into_iter().map(|(path, _)| path).collect();
+                tree = odt::query::extract(&tree, &paths);
+            }
             if args.sort {
                 tree.sort();
             }
@@ -135,18 +257,20 @@ fn dts_input(args: Args) -> Result<(), Box<dyn std::error::Error>> {
         Format::Dtv => {
             // Lower all the way to binary node values, then convert back into source.
             // Types are lost in this process.
-            let mut tree = odt::compile(&loader, &arena, &[&input], &mut scribe);
+            let mut tree = compile(&mut scribe);
+            if let Some(selector) = &selector {
+                tree = select_binary(&tree, selector);
+            }
             if args.sort {
                 tree.sort();
             }
-            let source = format!("/dts-v1/;/{tree};");
-            // Reparse and pretty-print the output.
-            let tree = odt::parse::parse_untyped(&source).unwrap();
-            let output = odt::print::format(tree);
-            output.into_bytes()
+            odt::print::format_binary(&tree).into_bytes()
         }
     };
     let ok = scribe.report(&loader, &mut std::io::stderr());
+    if let (Some(path), Some(build_cache)) = (&args.cache, &build_cache) {
+        build_cache.save(path)?;
+    }
     let (goal, mut writer) = open_output(args.out)?;
     writer.write_all(&bytes)?;
     if let Some(depfile) = args.out_dependency {
@@ -160,6 +284,205 @@ fn dts_input(args: Args) -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
+/// Like the unannotated DTS or DTV output, but retain the records of each node and property's
+/// definitions in order to annotate them.
+fn annotated_source(
+    loader: &impl odt::fs::Loader,
+    arena: &odt::Arena,
+    input: &std::path::Path,
+    evaluate: bool,
+    sort: bool,
+    scribe: &mut odt::error::Scribe,
+) -> String {
+    use odt::provenance::annotate;
+    let dts = odt::parse::parse_with_includes(loader, arena, input, scribe);
+    let (mut tree, node_labels, node_changes, prop_changes) = odt::merge::merge(&dts, scribe);
+    if evaluate {
+        let tree = odt::eval::resolve_incbin_paths(loader, arena, tree, scribe);
+        let mut tree = odt::eval::eval(tree, node_labels, loader, scribe);
+        if sort {
+            tree.sort();
+        }
+        let body = annotate(&tree, &node_changes, &prop_changes, loader);
+        format!("/dts-v1/;/{body};")
+    } else {
+        if sort {
+            tree.sort();
+        }
+        let body = annotate(&tree, &node_changes, &prop_changes, loader);
+        format!("/dts-v1/;{}/{body};", tree.labels_as_display())
+    }
+}
+
+fn print_history(
+    queries: &[String],
+    loader: &impl odt::fs::Loader,
+    arena: &odt::Arena,
+    input: &std::path::Path,
+    mut scribe: odt::error::Scribe,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dts = odt::parse::parse_with_includes(loader, arena, input, &mut scribe);
+    let (_, node_labels, node_changes, prop_changes) = odt::merge::merge(&dts, &mut scribe);
+    let ok = scribe.report(loader, &mut std::io::stderr());
+    for query in queries {
+        let path = match query.strip_prefix('&') {
+            Some(reference) => {
+                let (label, rest) = reference.split_once('/').unwrap_or((reference, ""));
+                let Some(path) = node_labels.get(label) else {
+                    return Err(format!("{query}: no such label").into());
+                };
+                path.join(rest)
+            }
+            None => odt::path::NodePath::root().join(query),
+        };
+        match odt::provenance::lookup(&node_changes, &prop_changes, &path) {
+            Some(provenance) => print!("{}", provenance.describe(loader)),
+            None => return Err(format!("{query}: no such node or property").into()),
+        }
+    }
+    if ok {
+        Ok(())
+    } else {
+        Err("compilation failed".into())
+    }
+}
+
+fn print_shadowed(
+    loader: &impl odt::fs::Loader,
+    arena: &odt::Arena,
+    input: &std::path::Path,
+    mut scribe: odt::error::Scribe,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use odt::provenance::{describe_shadowed, shadowed};
+    let dts = odt::parse::parse_with_includes(loader, arena, input, &mut scribe);
+    let (_, _, node_changes, prop_changes) = odt::merge::merge(&dts, &mut scribe);
+    let ok = scribe.report(loader, &mut std::io::stderr());
+    let shadowed = shadowed(&node_changes, &prop_changes);
+    print!("{}", describe_shadowed(&shadowed, loader));
+    if ok {
+        Ok(())
+    } else {
+        Err("compilation failed".into())
+    }
+}
+
+fn fix_sources(
+    checks: &odt::checks::Registry,
+    loader: &impl odt::fs::Loader,
+    search_path: Vec<PathBuf>,
+    arena: &odt::Arena,
+    input: &std::path::Path,
+    mut scribe: odt::error::Scribe,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use odt::fs::{Loader, LocalFileLoader};
+    odt::compile_checked(loader, arena, &[input], checks, &mut scribe);
+    let fixes = scribe.fixes();
+    let mut before: Vec<String> = scribe
+        .messages()
+        .iter()
+        .map(|m| diagnostic_key(m))
+        .collect();
+    let ok = scribe.report(loader, &mut std::io::stderr());
+    let files = odt::print::apply_fixes(loader, &fixes)?;
+    if files
+        .iter()
+        .any(|file| file.path.as_os_str() == LocalFileLoader::STDIN)
+    {
+        return Err("cannot fix stdin".into());
+    }
+    for file in &files {
+        if file.skipped > 0 {
+            eprintln!(
+                "{}: {} fixes overlapped others and were not applied; run again to apply them",
+                file.path.display(),
+                file.skipped
+            );
+        }
+    }
+
+    // Make sure the fixed sources compile, and report nothing new, before overwriting anything.
+    let mut fixed = LocalFileLoader::new(search_path);
+    for file in &files {
+        fixed.insert(file.path.clone(), file.text.clone().into_bytes());
+    }
+    let fixed_arena = odt::Arena::new();
+    let mut fixed_scribe = odt::error::Scribe::new(false);
+    odt::compile_checked(&fixed, &fixed_arena, &[input], checks, &mut fixed_scribe);
+    let (warnings, errors) = fixed_scribe.into_inner();
+    let mut new = vec![];
+    for diagnostic in errors.into_iter().chain(warnings) {
+        let key = diagnostic_key(&diagnostic.pest_error.variant.message());
+        match before.iter().position(|k| *k == key) {
+            Some(i) => _ = before.swap_remove(i),
+            None => new.push(diagnostic),
+        }
+    }
+    if !new.is_empty() {
+        for diagnostic in new {
+            eprintln!("Error: {}", fixed.annotate_error(diagnostic));
+        }
+        return Err(
+            "the fixed sources report problems the original did not; no files were changed".into(),
+        );
+    }
+
+    // Write every file before renaming any, so that a failure leaves the sources unchanged.
+    let mut written = vec![];
+    for file in &files {
+        let tmp = odt::fs::temp_path(&file.path);
+        let result = std::fs::write(&tmp, &file.text);
+        written.push(tmp);
+        if let Err(e) = result {
+            for tmp in written {
+                let _ = std::fs::remove_file(tmp);
+            }
+            return Err(e.into());
+        }
+    }
+    for (tmp, file) in written.into_iter().zip(&files) {
+        std::fs::rename(tmp, &file.path)?;
+    }
+    if ok {
+        Ok(())
+    } else {
+        Err("compilation failed".into())
+    }
+}
+
+/// A diagnostic's message, without the path of the node or property a check reports it on,
+/// which a fix may have renamed.
+fn diagnostic_key(message: &str) -> String {
+    if message.starts_with('(')
+        && let Some((check, rest)) = message.split_once(") ")
+        && let Some((_, finding)) = rest.split_once(": ")
+    {
+        format!("{check}) {finding}")
+    } else {
+        message.to_owned()
+    }
+}
+
+/// The selector given by --select, if any.
+fn selector(args: &Args) -> Result<Option<Selector>, String> {
+    args.select.as_deref().map(Selector::parse).transpose()
+}
+
+/// The nodes of `tree` matching `selector`, and their ancestors.
+fn select_binary(tree: &odt::BinaryNode, selector: &Selector) -> odt::BinaryNode {
+    let selected = Query::binary(tree).select(selector);
+    let paths: Vec<_> = selected.into_iter().map(|(path, _)| path).collect();
+    odt::query::extract(tree, &paths)
+}
+
+/// The checks to run, configured by -W and -E.
+fn checks(args: &Args) -> Result<odt::checks::Registry, String> {
+    #[cfg(feature = "schema")]
+    let schema = args.schema.as_deref();
+    #[cfg(not(feature = "schema"))]
+    let schema = None;
+    odt::checks::Registry::from_flags(schema, &args.warning, &args.error)
+}
+
 fn open_output(
     out: Option<PathBuf>,
 ) -> Result<(String, Box<dyn Write>), Box<dyn std::error::Error>> {
@@ -0,0 +1,84 @@
+use clap::Parser as _;
+use odt::fdtutil::{Kind, ValueType, find_node, format_value};
+use std::io::{BufWriter, Read, Write};
+use std::path::PathBuf;
+
+/// Read properties from a DTB, as in `fdtget board.dtb /chosen bootargs`.
+#[derive(clap::Parser)]
+#[command(version, args_override_self = true)]
+struct Args {
+    /// Input file ("-" for stdin)
+    #[arg(value_name = "dtb_path")]
+    dtb_path: PathBuf,
+
+    /// Pairs of a node and one of its properties, or with -p or -l, nodes.  A node is a path, or
+    /// an alias optionally followed by a path, as in `serial0` or `i2c1/eeprom@50`.
+    #[arg(value_name = "node property", required = true)]
+    args: Vec<String>,
+
+    /// Type of the values:  an optional size (hh or b for bytes, h for 16 bits, l for 32 bits)
+    /// followed by s (strings), i (signed), u (unsigned), x (hex), or r (raw bytes).  By default
+    /// the type is guessed from the value.
+    #[arg(short = 't', long = "type", value_name = "type")]
+    value_type: Option<ValueType>,
+
+    /// List the properties of each node
+    #[arg(short = 'p', long, conflicts_with = "list")]
+    properties: bool,
+
+    /// List the subnodes of each node
+    #[arg(short = 'l', long)]
+    list: bool,
+
+    /// Value to show for missing properties
+    #[arg(short = 'd', long, value_name = "value")]
+    default: Option<String>,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let blob = match args.dtb_path.as_os_str() == "-" {
+        true => {
+            let mut blob = vec![];
+            std::io::stdin().read_to_end(&mut blob)?;
+            blob
+        }
+        false => std::fs::read(&args.dtb_path)?,
+    };
+    let tree = odt::flat::deserialize(&blob)?;
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    if args.properties || args.list {
+        for path in &args.args {
+            let node = find_node(&tree, path)?;
+            if args.properties {
+                for (name, _) in node.properties() {
+                    writeln!(out, "{name}")?;
+                }
+            } else {
+                for (name, _) in node.children() {
+                    writeln!(out, "{name}")?;
+                }
+            }
+        }
+        return Ok(out.flush()?);
+    }
+    if !args.args.len().is_multiple_of(2) {
+        return Err("expected pairs of a node and a property".into());
+    }
+    for pair in args.args.chunks_exact(2) {
+        let [path, name] = pair else { unreachable!() };
+        let node = find_node(&tree, path)?;
+        let value = match (node.get_property(name), &args.default) {
+            (Some(value), _) => {
+                format_value(value, args.value_type).map_err(|e| format!("{path}: {name}: {e}"))?
+            }
+            (None, Some(default)) => default.clone().into_bytes(),
+            (None, None) => return Err(format!("{path}: no property {name}").into()),
+        };
+        out.write_all(&value)?;
+        if args.value_type.is_none_or(|t| t.kind != Kind::Raw) {
+            writeln!(out)?;
+        }
+    }
+    Ok(out.flush()?)
+}
@@ -0,0 +1,79 @@
+use clap::Parser as _;
+use odt::fdtutil::{
+    Kind, ValueType, create_node, delete_properties, encode_value, node_mut, remove_node,
+};
+use std::path::PathBuf;
+
+/// Edit a DTB in place, as in `fdtput -t s board.dtb /chosen bootargs "console=ttyS0"`.
+#[derive(clap::Parser)]
+#[command(version, args_override_self = true)]
+struct Args {
+    /// File to edit
+    #[arg(value_name = "dtb_path")]
+    dtb_path: PathBuf,
+
+    /// A node, a property, and its values; or with -c or -r, nodes; or with -d, a node and its
+    /// properties.  A node is a path, or an alias optionally followed by a path, as in `serial0`
+    /// or `i2c1/eeprom@50`.
+    #[arg(
+        value_name = "node property value",
+        required = true,
+        allow_negative_numbers = true
+    )]
+    args: Vec<String>,
+
+    /// Type of the values:  an optional size (hh or b for bytes, h for 16 bits, l for 32 bits)
+    /// followed by s (strings), i (signed), u (unsigned), or x (hex).  By default, values are
+    /// 32-bit signed integers.
+    #[arg(short = 't', long = "type", value_name = "type")]
+    value_type: Option<ValueType>,
+
+    /// Create missing nodes along the path
+    #[arg(short = 'p', long)]
+    auto_path: bool,
+
+    /// Create nodes
+    #[arg(short = 'c', long, conflicts_with_all = ["remove", "delete"])]
+    create: bool,
+
+    /// Remove nodes and their subnodes
+    #[arg(short = 'r', long, conflicts_with = "delete")]
+    remove: bool,
+
+    /// Delete properties
+    #[arg(short = 'd', long)]
+    delete: bool,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let blob = std::fs::read(&args.dtb_path)?;
+    let mut fdt = odt::flat::deserialize_fdt(&blob)?;
+    let tree = &mut fdt.root;
+    if args.create {
+        for path in &args.args {
+            create_node(tree, path, args.auto_path)?;
+        }
+    } else if args.remove {
+        for path in &args.args {
+            remove_node(tree, path)?;
+        }
+    } else if args.delete {
+        let [path, names @ ..] = &args.args[..] else {
+            unreachable!("clap requires an argument");
+        };
+        delete_properties(tree, path, names)?;
+    } else {
+        let [path, name, values @ ..] = &args.args[..] else {
+            return Err("expected a node, a property, and its values".into());
+        };
+        let value_type = args.value_type.unwrap_or(ValueType {
+            kind: Kind::Signed,
+            size: None,
+        });
+        let value = encode_value(values, value_type).map_err(|e| format!("{path}: {name}: {e}"))?;
+        node_mut(tree, path, false, args.auto_path)?.set_property(name, value);
+    }
+    odt::fs::write_atomic(&args.dtb_path, &odt::flat::serialize_fdt(&fdt))?;
+    Ok(())
+}
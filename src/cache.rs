@@ -0,0 +1,421 @@
+//! Facilities for reusing compilation results across runs.
+//!
+//! A `BuildCache` remembers the output of each compiled target along with every file request
+//! made while compiling it, and the digest of each file found.  A later compilation of the same
+//! target replays those requests; if each finds the same file with the same contents, the cached
+//! tree is returned without parsing or evaluating anything.  The cache may be saved to disk and
+//! loaded by another process.
+//!
+//! When an input has changed, the target is compiled again, but the evaluated property
+//! values of each node are also cached, keyed on a digest of their source text and of the paths,
+//! phandles and `/incbin/` files they refer to.  Nodes whose key is unchanged, such as every node
+//! of an unchanged subtree that does not use property references, reuse their values without
+//! evaluating them.  Phandles are assigned in order across the whole tree, so an edit which
+//! renumbers them also invalidates the nodes which refer to the renumbered ones.
+//!
+//! Parsed files are reused within a process by `parse::ParseCache`.
+
+use crate::BinaryNode;
+use crate::fs::{Access, AccessResult, Loader};
+use sha2::{Digest as _, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+/// SHA-256 of a file's contents.
+pub type Digest = [u8; 32];
+
+pub fn digest(bytes: &[u8]) -> Digest {
+    Sha256::digest(bytes).into()
+}
+
+struct BuildEntry {
+    /// Each request made during compilation, with the path found and the digest of its contents.
+    accesses: Vec<(Access, Option<(PathBuf, Digest)>)>,
+    tree: BinaryNode,
+    /// Keys of the node values used while compiling, which are kept as long as this entry is.
+    nodes: Vec<Digest>,
+}
+
+/// Compiled trees keyed on their input paths and a description of the compiler's configuration,
+/// valid as long as their inputs are unchanged.
+#[derive(Default)]
+pub struct BuildCache {
+    entries: HashMap<(Vec<PathBuf>, String), BuildEntry>,
+    /// Evaluated property values of a node, in order, keyed as described in the module docs.
+    nodes: HashMap<Digest, Vec<Vec<u8>>>,
+    /// Keys of the node values looked up or inserted since the last call to `get()`.
+    touched: Vec<Digest>,
+}
+
+const MAGIC: &[u8; 16] = b"odt-build-cache\0";
+const VERSION: u32 = 3;
+
+impl BuildCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of cached targets.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The number of nodes whose evaluated values are cached.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The evaluated property values of a node with digest `key`, if cached.
+    pub(crate) fn node_values(&mut self, key: &Digest) -> Option<&[Vec<u8>]> {
+        let values = self.nodes.get(key)?;
+        self.touched.push(*key);
+        Some(values)
+    }
+
+    pub(crate) fn insert_node_values(&mut self, key: Digest, values: Vec<Vec<u8>>) {
+        self.touched.push(key);
+        self.nodes.insert(key, values);
+    }
+
+    /// Look up the tree compiled from `dts_paths` with configuration `config`.  The requests made
+    /// by the original compilation are repeated through `loader`, and the entry is used only if
+    /// they have the same results.
+    pub fn get(
+        &mut self,
+        loader: &impl Loader,
+        dts_paths: &[&Path],
+        config: &str,
+    ) -> Option<BinaryNode> {
+        self.touched.clear();
+        let entry = self.entries.get(&key(dts_paths, config))?;
+        for (access, expected) in &entry.accesses {
+            let found = access
+                .replay(loader)
+                .map(|(path, bytes)| (path, digest(bytes)));
+            let unchanged = match (found, expected) {
+                (None, None) => true,
+                (Some((path, d)), Some((old_path, old_d))) => path == old_path && d == *old_d,
+                _ => false,
+            };
+            if !unchanged {
+                return None;
+            }
+        }
+        Some(entry.tree.clone())
+    }
+
+    /// Record the tree compiled from `dts_paths` with configuration `config`, and the requests
+    /// made while compiling it.  Node values used since the last call to `get()` are kept along
+    /// with it.
+    pub fn insert(
+        &mut self,
+        dts_paths: &[&Path],
+        config: &str,
+        accesses: &[(Access, AccessResult)],
+        tree: &BinaryNode,
+    ) {
+        let accesses = accesses
+            .iter()
+            .map(|(access, result)| {
+                let found = result.map(|(path, bytes)| (path.to_owned(), digest(bytes)));
+                (access.clone(), found)
+            })
+            .collect();
+        let tree = tree.clone();
+        let nodes = std::mem::take(&mut self.touched);
+        self.entries.insert(
+            key(dts_paths, config),
+            BuildEntry {
+                accesses,
+                tree,
+                nodes,
+            },
+        );
+    }
+
+    /// Read a cache previously written by `save()`.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let mut r = &bytes[..];
+        let mut magic = [0; 16];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u32(&mut r)? != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, "not a build cache"));
+        }
+        let mut entries = HashMap::new();
+        for _ in 0..read_u32(&mut r)? {
+            let paths = (0..read_u32(&mut r)?)
+                .map(|_| read_path(&mut r))
+                .collect::<Result<_, _>>()?;
+            let key = (paths, read_string(&mut r)?);
+            let mut accesses = vec![];
+            for _ in 0..read_u32(&mut r)? {
+                let access = match read_u8(&mut r)? {
+                    0 => Access::Find(read_path(&mut r)?, read_path(&mut r)?),
+                    1 => Access::Read(read_path(&mut r)?),
+                    _ => return Err(Error::new(ErrorKind::InvalidData, "bad access kind")),
+                };
+                let found = match read_u8(&mut r)? {
+                    0 => None,
+                    _ => Some((read_path(&mut r)?, read_digest(&mut r)?)),
+                };
+                accesses.push((access, found));
+            }
+            let tree = read_tree(&mut r)?;
+            let nodes = (0..read_u32(&mut r)?)
+                .map(|_| read_digest(&mut r))
+                .collect::<Result<_, _>>()?;
+            entries.insert(
+                key,
+                BuildEntry {
+                    accesses,
+                    tree,
+                    nodes,
+                },
+            );
+        }
+        let mut nodes = HashMap::new();
+        for _ in 0..read_u32(&mut r)? {
+            let key = read_digest(&mut r)?;
+            let values = (0..read_u32(&mut r)?)
+                .map(|_| read_bytes(&mut r))
+                .collect::<Result<_, _>>()?;
+            nodes.insert(key, values);
+        }
+        Ok(Self {
+            entries,
+            nodes,
+            touched: vec![],
+        })
+    }
+
+    /// Write the cache to a file.  Paths which are not valid UTF-8 are stored lossily, so entries
+    /// which depend on them will never be reused.  Node values are written only if some target
+    /// used them when it was last compiled.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut w = vec![];
+        w.extend(MAGIC);
+        write_u32(&mut w, VERSION);
+        write_u32(&mut w, self.entries.len() as u32);
+        for ((paths, config), entry) in &self.entries {
+            write_u32(&mut w, paths.len() as u32);
+            for p in paths {
+                write_path(&mut w, p);
+            }
+            write_bytes(&mut w, config.as_bytes());
+            write_u32(&mut w, entry.accesses.len() as u32);
+            for (access, found) in &entry.accesses {
+                match access {
+                    Access::Find(relative_to, included_path) => {
+                        w.push(0);
+                        write_path(&mut w, relative_to);
+                        write_path(&mut w, included_path);
+                    }
+                    Access::Read(p) => {
+                        w.push(1);
+                        write_path(&mut w, p);
+                    }
+                }
+                match found {
+                    None => w.push(0),
+                    Some((p, d)) => {
+                        w.push(1);
+                        write_path(&mut w, p);
+                        w.extend(d);
+                    }
+                }
+            }
+            write_tree(&mut w, &entry.tree);
+            write_u32(&mut w, entry.nodes.len() as u32);
+            for key in &entry.nodes {
+                w.extend(key);
+            }
+        }
+        let live: HashSet<&Digest> = self.entries.values().flat_map(|e| &e.nodes).collect();
+        write_u32(&mut w, live.len() as u32);
+        for key in live {
+            let values = &self.nodes[key];
+            w.extend(key);
+            write_u32(&mut w, values.len() as u32);
+            for value in values {
+                write_bytes(&mut w, value);
+            }
+        }
+        crate::fs::write_atomic(path, &w)
+    }
+}
+
+fn key(dts_paths: &[&Path], config: &str) -> (Vec<PathBuf>, String) {
+    let paths = dts_paths.iter().map(|&p| p.to_owned()).collect();
+    (paths, config.to_owned())
+}
+
+fn write_u32(w: &mut Vec<u8>, n: u32) {
+    w.extend(n.to_be_bytes());
+}
+
+fn write_bytes(w: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(w, bytes.len() as u32);
+    w.extend(bytes);
+}
+
+fn write_path(w: &mut Vec<u8>, path: &Path) {
+    write_bytes(w, path.to_string_lossy().as_bytes());
+}
+
+fn write_tree(w: &mut Vec<u8>, node: &BinaryNode) {
+    let labels: Vec<&String> = node.labels().collect();
+    write_u32(w, labels.len() as u32);
+    for label in labels {
+        write_bytes(w, label.as_bytes());
+    }
+    write_u32(w, node.properties().count() as u32);
+    for (name, value) in node.properties() {
+        write_bytes(w, name.as_bytes());
+        write_bytes(w, value);
+    }
+    write_u32(w, node.children().count() as u32);
+    for (name, child) in node.children() {
+        write_bytes(w, name.as_bytes());
+        write_tree(w, child);
+    }
+}
+
+fn read_u8(r: &mut &[u8]) -> std::io::Result<u8> {
+    let mut b = [0; 1];
+    r.read_exact(&mut b)?;
+    Ok(b[0])
+}
+
+fn read_u32(r: &mut &[u8]) -> std::io::Result<u32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_be_bytes(b))
+}
+
+fn read_bytes(r: &mut &[u8]) -> std::io::Result<Vec<u8>> {
+    let n = read_u32(r)? as usize;
+    let Some((bytes, rest)) = r.split_at_checked(n) else {
+        return Err(ErrorKind::UnexpectedEof.into());
+    };
+    *r = rest;
+    Ok(bytes.to_vec())
+}
+
+fn read_string(r: &mut &[u8]) -> std::io::Result<String> {
+    String::from_utf8(read_bytes(r)?).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+fn read_path(r: &mut &[u8]) -> std::io::Result<PathBuf> {
+    read_string(r).map(PathBuf::from)
+}
+
+fn read_digest(r: &mut &[u8]) -> std::io::Result<Digest> {
+    let mut d = Digest::default();
+    r.read_exact(&mut d)?;
+    Ok(d)
+}
+
+fn read_tree(r: &mut &[u8]) -> std::io::Result<BinaryNode> {
+    let mut node = BinaryNode::default();
+    for _ in 0..read_u32(r)? {
+        node.add_label(&read_string(r)?);
+    }
+    for _ in 0..read_u32(r)? {
+        let name = read_string(r)?;
+        node.set_property(&name, read_bytes(r)?);
+    }
+    for _ in 0..read_u32(r)? {
+        let name = read_string(r)?;
+        *node.add_child(&name) = read_tree(r)?;
+    }
+    Ok(node)
+}
+
+#[test]
+fn test_build_cache() {
+    use crate::fs::{LocalFileLoader, TrackingLoader};
+    let dir = std::env::temp_dir().join(format!("odt-test-build-cache-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let board = dir.join("board.dts");
+    let soc = dir.join("soc.dtsi");
+    std::fs::write(
+        &board,
+        "/include/ \"soc.dtsi\"\n/ { x: board { y = <1>; }; };",
+    )
+    .unwrap();
+    std::fs::write(&soc, "/dts-v1/; / { soc { z = <&x>; }; };").unwrap();
+
+    let compile = |cache: &mut BuildCache| {
+        let files = LocalFileLoader::new(vec![]);
+        let loader = TrackingLoader::new(&files);
+        let arena = crate::Arena::new();
+        let mut parse_cache = crate::parse::ParseCache::default();
+        let mut scribe = crate::error::Scribe::new(true);
+        let paths: &[&Path] = &[&board];
+        let tree = crate::compile_cached(
+            &loader,
+            &arena,
+            paths,
+            &mut parse_cache,
+            Some(cache),
+            &crate::checks::Registry::default(),
+            &mut scribe,
+        );
+        assert!(scribe.report(&loader, &mut std::io::stderr()));
+        (tree, parse_cache.len())
+    };
+
+    let mut cache = BuildCache::new();
+    let (tree, parsed) = compile(&mut cache);
+    assert_eq!(parsed, 2);
+    assert_eq!(cache.len(), 1);
+    assert_eq!(cache.node_count(), 2);
+
+    // A hit parses nothing, and survives a round trip through the filesystem.
+    let saved = dir.join("cache.tmp");
+    cache.save(&saved).unwrap();
+    let mut names = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name());
+    assert!(names.all(|name| !name.to_string_lossy().starts_with('.')));
+    let mut cache = BuildCache::load(&saved).unwrap();
+    assert_eq!(cache.node_count(), 2);
+    let (cached, parsed) = compile(&mut cache);
+    assert_eq!(parsed, 0);
+    assert_eq!(
+        crate::flat::serialize(&cached),
+        crate::flat::serialize(&tree)
+    );
+    assert_eq!(
+        cached.walk(["board"]).unwrap().labels().next().unwrap(),
+        "x"
+    );
+
+    // Changing an include invalidates the entry, but not the values of the unchanged node.
+    // Alter the cached value of y, to show that it is not evaluated again.
+    std::fs::write(&soc, "/dts-v1/; / { soc { z = <&x 2>; }; };").unwrap();
+    for values in cache.nodes.values_mut() {
+        for value in values.iter_mut().filter(|v| v[..] == [0, 0, 0, 1]) {
+            *value = vec![0, 0, 0, 9];
+        }
+    }
+    let (changed, parsed) = compile(&mut cache);
+    assert_eq!(parsed, 2);
+    assert_eq!(cache.node_count(), 3);
+    let z = changed.walk(["soc"]).unwrap().get_property("z").unwrap();
+    assert_eq!(z.len(), 8);
+    let y = changed.walk(["board"]).unwrap().get_property("y").unwrap();
+    assert_eq!(y, &[0, 0, 0, 9]);
+
+    // Values no longer used by any target are not saved.
+    cache.save(&saved).unwrap();
+    assert_eq!(BuildCache::load(&saved).unwrap().node_count(), 2);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,604 @@
+//! Semantic checks of a compiled tree, in the spirit of dtc's checks.
+//!
+//! A `Check` inspects the evaluated tree (and, when compiling from source, the parsed source and
+//! the records of where each node and property was defined), reporting problems through a
+//! `Reporter`.  A `Registry` holds the checks to run and the severity of each.
+//! `Registry::default()` contains the built-in checks; other crates may add their own with
+//! `Registry::register()`.
+
+mod aliases;
+mod buses;
+mod cpus;
+mod graph;
+mod interrupts;
+mod labels;
+mod lists;
+mod names;
+mod phandles;
+mod reg;
+mod types;
+
+pub use phandles::{PHANDLE_ARGS, PhandleArgs};
+
+use crate::BinaryNode;
+use crate::error::{Edit, Scribe, SourceError};
+use crate::interrupts::InterruptResolver;
+use crate::label::{LabelMap, LabelResolver};
+use crate::merge::{NodeChange, NodeChanges, PropChange, PropChanges};
+use crate::parse::TypedRuleExt;
+use crate::parse::rules::{Dts, TypedRule};
+use crate::path::NodePath;
+use crate::provenance::node_history;
+use core::cell::OnceCell;
+use core::fmt::Display;
+use pest::Span;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The severity of a check's findings.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Level {
+    Off,
+    Warning,
+    Error,
+}
+
+pub trait Check: Send + Sync {
+    /// The name used to configure the check, as in `-W no-<name>`.  By convention this matches
+    /// the name of the equivalent dtc check, if there is one.
+    fn name(&self) -> &'static str;
+
+    /// The severity of the check's findings unless configured otherwise.
+    fn default_level(&self) -> Level {
+        Level::Warning
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter);
+
+    /// Describes any configuration of the check besides its level, such as the contents of files
+    /// it reads, so that `Registry::fingerprint()` changes with it.
+    fn fingerprint(&self) -> String {
+        String::new()
+    }
+}
+
+/// The products of compiling from source, which are unavailable when the tree was decoded from
+/// a blob.
+pub struct Source<'t, 'a> {
+    /// The parsed source, with includes expanded.
+    pub dts: &'t Dts<'a>,
+    pub node_changes: &'t NodeChanges<'a>,
+    pub prop_changes: &'t PropChanges<'a>,
+}
+
+/// Everything a check may inspect.
+pub struct Context<'t, 'a> {
+    /// The evaluated tree.
+    pub tree: &'t BinaryNode,
+    pub source: Option<Source<'t, 'a>>,
+    phandles: HashMap<u32, NodePath>,
+    /// The path of each labeled node, collected on first use.
+    label_paths: OnceCell<LabelMap>,
+    /// Label definitions and references in the source, gathered on first use.
+    source_labels: OnceCell<Option<labels::Labels<'a>>>,
+}
+
+impl<'t, 'a> Context<'t, 'a> {
+    pub fn new(tree: &'t BinaryNode, source: Option<Source<'t, 'a>>) -> Self {
+        Self {
+            tree,
+            source,
+            phandles: tree.phandles(),
+            label_paths: OnceCell::new(),
+            source_labels: OnceCell::new(),
+        }
+    }
+
+    /// Every node of the evaluated tree with its path, parents before children.
+    pub fn nodes(&self) -> Vec<(NodePath, &'t BinaryNode)> {
+        let mut nodes = vec![];
+        self.tree
+            .for_each_node(&NodePath::root(), &mut |path, node| {
+                nodes.push((path.clone(), node))
+            });
+        nodes
+    }
+
+    /// The parent of the node at `path`, or `None` for the root.
+    pub fn parent(&self, path: &NodePath) -> Option<&'t BinaryNode> {
+        if path.is_root() {
+            return None;
+        }
+        self.tree.walk(path.parent().segments())
+    }
+
+    /// Resolve a path string such as `/soc/serial@1000`, or one starting with a label when
+    /// compiling from source, as in `uart0/child`.
+    pub fn resolve_path(&self, path: &str) -> Option<NodePath> {
+        let labels = self.label_paths.get_or_init(|| {
+            let mut labels = LabelMap::new();
+            self.tree
+                .for_each_node(&NodePath::root(), &mut |path, node| {
+                    for label in node.labels() {
+                        labels.insert(label.clone(), path.clone());
+                    }
+                });
+            labels
+        });
+        LabelResolver(labels, self.tree).resolve_str(&NodePath::root(), path)
+    }
+
+    /// Find the node with the given phandle.
+    pub fn phandle_target(&self, phandle: u32) -> Option<(&NodePath, &'t BinaryNode)> {
+        let path = self.phandles.get(&phandle)?;
+        Some((path, self.tree.walk(path.segments())?))
+    }
+
+    /// Resolves interrupts through the tree's phandles.
+    pub fn interrupt_resolver(&self) -> InterruptResolver<'_> {
+        InterruptResolver(&self.phandles, self.tree)
+    }
+
+    /// Find the interrupt parent of the node at `path`; see `InterruptResolver::interrupt_parent`.
+    pub fn interrupt_parent(&self, path: &NodePath) -> Result<(NodePath, &BinaryNode), String> {
+        InterruptResolver(&self.phandles, self.tree).interrupt_parent(path)
+    }
+
+    /// Split `list` into phandles and their arguments, where each provider gives its number of
+    /// arguments in its `cells` property (or else `default_cells`, if supplied).  Null phandles
+    /// (0 or 0xffffffff) are placeholders without arguments, and are skipped.
+    pub fn phandle_args(
+        &self,
+        list: &[u32],
+        cells: &str,
+        default_cells: Option<u32>,
+    ) -> Result<Vec<Specifier<'t>>, String> {
+        let entries = self.phandle_entries(list, cells, default_cells)?;
+        Ok(entries.into_iter().flatten().collect())
+    }
+
+    /// Like `phandle_args`, but keeping null phandles as `None`, so that each entry of `list`
+    /// has an index, as counted by a corresponding `*-names` property.
+    pub fn phandle_entries(
+        &self,
+        list: &[u32],
+        cells: &str,
+        default_cells: Option<u32>,
+    ) -> Result<Vec<Option<Specifier<'t>>>, String> {
+        let mut entries = vec![];
+        let mut rest = list;
+        while let Some((&phandle, tail)) = rest.split_first() {
+            let entry = entries.len();
+            rest = tail;
+            if phandle == 0 || phandle == u32::MAX {
+                entries.push(None);
+                continue;
+            }
+            let Some((provider, node)) = self.phandle_target(phandle) else {
+                return Err(format!("entry {entry}: no node has phandle {phandle:#x}"));
+            };
+            let Some(n) = node.get_u32(cells).or(default_cells) else {
+                return Err(format!("entry {entry}: {provider} has no {cells} property"));
+            };
+            let Some((args, tail)) = rest.split_at_checked(n as usize) else {
+                let found = rest.len();
+                return Err(format!(
+                    "entry {entry}: {provider} has {cells} = <{n}>, but {found} cells remain"
+                ));
+            };
+            rest = tail;
+            entries.push(Some(Specifier {
+                provider: provider.clone(),
+                node,
+                args: args.to_vec(),
+            }));
+        }
+        Ok(entries)
+    }
+
+    /// An edit renaming the node at `path` to `name`, if it came from source where a single
+    /// definition names it, as in `uart@1000 { };` (others may refer to it by label), and
+    /// nothing names it or a descendant by path:  neither a reference, as in `&{/uart@1000}`,
+    /// nor a string, as in `/aliases` or `/chosen/stdout-path`.  A sibling named `name` would
+    /// be merged with it, so that also prevents the rename.
+    pub fn rename_node(&self, path: &NodePath, name: &str) -> Option<Edit> {
+        let changes = self.source.as_ref()?.node_changes.get(path)?;
+        let taken = self.parent(path)?.get_child(name).is_some();
+        if taken || self.referenced_by_path(path.leaf()) || self.named_by_string(path) {
+            return None;
+        }
+        let by_label = |c: &&NodeChange| match c {
+            NodeChange::TopNode(node) => {
+                let name = node.top_node_name.str();
+                name.starts_with('&') && !name.starts_with("&{")
+            }
+            _ => false,
+        };
+        let mut named = changes.iter().filter(|c| !by_label(c));
+        match (named.next(), named.next()) {
+            (Some(NodeChange::ChildNode(node)), None) => {
+                Some(Edit::new(*node.node_name.span(), name))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a string property names the node at `path` or a descendant by its full path.
+    /// Any options following ':' are ignored, as in `stdout-path = "/serial@1000:115200n8"`.
+    fn named_by_string(&self, path: &NodePath) -> bool {
+        self.nodes().into_iter().any(|(_, node)| {
+            node.properties().any(|(name, _)| {
+                let strings = node.get_strings(name).unwrap_or_default();
+                strings.iter().any(|s| {
+                    let s = s.split(':').next().unwrap();
+                    s.starts_with('/') && self.resolve_path(s).is_some_and(|p| p.starts_with(path))
+                })
+            })
+        })
+    }
+
+    /// The name of the definition which created the node at `path`, if it came from source.
+    pub fn node_span(&self, path: &NodePath) -> Option<Span<'a>> {
+        let changes = self.source.as_ref()?.node_changes.get(path)?;
+        let events = node_history(changes);
+        let created = events.iter().position(|e| e.effective)?;
+        changes[created].name_span().copied()
+    }
+
+    /// The definition which gave the property its value, if it came from source.
+    pub fn prop_span(&self, path: &NodePath, name: &str) -> Option<Span<'a>> {
+        let changes = self.source.as_ref()?.prop_changes.get(&path.join(name))?;
+        match changes.last()? {
+            PropChange::Prop(prop) => Some(*prop.span()),
+            _ => None,
+        }
+    }
+
+    /// The name in the definition which gave the property its value, if it came from source.
+    pub fn prop_name_span(&self, path: &NodePath, name: &str) -> Option<Span<'a>> {
+        let changes = self.source.as_ref()?.prop_changes.get(&path.join(name))?;
+        match changes.last()? {
+            PropChange::Prop(prop) => Some(*prop.prop_name.span()),
+            _ => None,
+        }
+    }
+}
+
+/// One entry of a list of phandles and arguments.
+pub struct Specifier<'t> {
+    pub provider: NodePath,
+    pub node: &'t BinaryNode,
+    pub args: Vec<u32>,
+}
+
+/// Receives the findings of one check, attributing them to source where possible.
+pub struct Reporter<'r, 't, 'a> {
+    ctx: &'r Context<'t, 'a>,
+    name: &'static str,
+    level: Level,
+    scribe: &'r mut Scribe,
+}
+
+impl<'a> Reporter<'_, '_, 'a> {
+    /// Report a problem with the node at `path`.
+    pub fn node(&mut self, path: &NodePath, message: impl Display) {
+        let span = self.ctx.node_span(path);
+        self.at(span, path, message);
+    }
+
+    /// Report a problem with the property `name` of the node at `path`.
+    pub fn property(&mut self, path: &NodePath, name: &str, message: impl Display) {
+        let span = self
+            .ctx
+            .prop_span(path, name)
+            .or_else(|| self.ctx.node_span(path));
+        self.at(span, &path.join(name), message);
+    }
+
+    /// Report a problem with the name of the property `name` of the node at `path`.
+    pub fn property_name(&mut self, path: &NodePath, name: &str, message: impl Display) {
+        let span = self
+            .ctx
+            .prop_name_span(path, name)
+            .or_else(|| self.ctx.node_span(path));
+        self.at(span, &path.join(name), message);
+    }
+
+    /// Like `node()`, but suggest an edit to fix the problem.
+    pub fn node_with_fix(&mut self, path: &NodePath, message: impl Display, fix: Option<Edit>) {
+        let span = self.ctx.node_span(path);
+        self.at_with_fix(span, path, message, fix);
+    }
+
+    /// Report a problem concerning `path`, pointing at `span` if known.
+    pub fn at(&mut self, span: Option<Span>, path: &NodePath, message: impl Display) {
+        self.at_with_fix(span, path, message, None);
+    }
+
+    /// Like `at()`, but suggest an edit to fix the problem.
+    pub fn at_with_fix(
+        &mut self,
+        span: Option<Span>,
+        path: &NodePath,
+        message: impl Display,
+        fix: Option<Edit>,
+    ) {
+        let message = format!("({}) {path}: {message}", self.name);
+        let mut err = match span {
+            Some(span) => SourceError::new(message, span),
+            None => SourceError::new_unattributed(message),
+        };
+        err.fixes.extend(fix);
+        match self.level {
+            Level::Off => (),
+            Level::Warning => self.scribe.warn(err),
+            Level::Error => self.scribe.err(err),
+        }
+    }
+}
+
+/// A set of checks, each with its configured severity.
+pub struct Registry {
+    checks: Vec<(Box<dyn Check>, Level)>,
+}
+
+impl Default for Registry {
+    /// The built-in checks.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        aliases::register(&mut registry);
+        buses::register(&mut registry);
+        cpus::register(&mut registry);
+        graph::register(&mut registry);
+        interrupts::register(&mut registry);
+        labels::register(&mut registry);
+        lists::register(&mut registry);
+        names::register(&mut registry);
+        phandles::register(&mut registry);
+        reg::register(&mut registry);
+        types::register(&mut registry);
+        registry
+    }
+}
+
+impl Registry {
+    /// A registry with no checks.
+    pub fn empty() -> Self {
+        Self { checks: vec![] }
+    }
+
+    /// Add a check at its default level.  Panics if a check of the same name is present.
+    pub fn register(&mut self, check: Box<dyn Check>) {
+        let name = check.name();
+        assert!(self.level(name).is_none(), "duplicate check {name}");
+        let level = check.default_level();
+        self.checks.push((check, level));
+    }
+
+    /// The names of the registered checks, in the order they run.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.checks.iter().map(|(check, _)| check.name())
+    }
+
+    pub fn level(&self, name: &str) -> Option<Level> {
+        let (_, level) = self.checks.iter().find(|(c, _)| c.name() == name)?;
+        Some(*level)
+    }
+
+    pub fn set_level(&mut self, name: &str, level: Level) -> Result<(), String> {
+        match self.checks.iter_mut().find(|(c, _)| c.name() == name) {
+            Some((_, l)) => *l = level,
+            None => return Err(format!("unknown check {name}")),
+        }
+        Ok(())
+    }
+
+    /// Apply a command-line option in the style of dtc.  `-W <name>` (with `level` Warning)
+    /// enables a check, as a warning unless it already reports errors, and `-W no-<name>`
+    /// disables it; `-E <name>` (with `level` Error) makes the check's findings errors, and
+    /// `-E no-<name>` demotes them to warnings.
+    pub fn configure(&mut self, option: &str, level: Level) -> Result<(), String> {
+        let Some(name) = option.strip_prefix("no-") else {
+            return match (level, self.level(option)) {
+                (Level::Warning, Some(Level::Error)) => Ok(()),
+                _ => self.set_level(option, level),
+            };
+        };
+        let current = self.level(name);
+        match (level, current) {
+            (_, None) => Err(format!("unknown check {name}")),
+            (Level::Error, Some(Level::Error)) => self.set_level(name, Level::Warning),
+            (Level::Error, Some(_)) => Ok(()),
+            _ => self.set_level(name, Level::Off),
+        }
+    }
+
+    /// The built-in checks, plus those of the bindings in `schema` (a directory of dt-schema
+    /// YAML files), configured by the command-line options `-W` and `-E` in that order.
+    pub fn from_flags(
+        schema: Option<&Path>,
+        warning: &[String],
+        error: &[String],
+    ) -> Result<Self, String> {
+        let mut checks = Self::default();
+        if let Some(dir) = schema {
+            #[cfg(feature = "schema")]
+            checks.register(Box::new(crate::schema::Schemas::load(dir)?));
+            #[cfg(not(feature = "schema"))]
+            return Err(format!(
+                "can't load {dir:?}: built without the schema feature"
+            ));
+        }
+        for option in warning {
+            checks.configure(option, Level::Warning).map_err(|e| {
+                format!(
+                    "{e} (-W names a check; for warnings as errors, see --treat-warnings-as-errors)"
+                )
+            })?;
+        }
+        for option in error {
+            checks.configure(option, Level::Error)?;
+        }
+        Ok(checks)
+    }
+
+    /// A string describing the configuration, which differs between registries which may
+    /// report different findings.
+    pub fn fingerprint(&self) -> String {
+        let mut enabled: Vec<String> = self
+            .checks
+            .iter()
+            .filter(|(_, level)| *level != Level::Off)
+            .map(|(check, level)| match check.fingerprint() {
+                fingerprint if fingerprint.is_empty() => format!("{}={level:?}", check.name()),
+                fingerprint => format!("{}={level:?}:{fingerprint}", check.name()),
+            })
+            .collect();
+        enabled.sort();
+        enabled.join(",")
+    }
+
+    /// Run each enabled check.
+    pub fn run(&self, ctx: &Context, scribe: &mut Scribe) {
+        for (check, level) in &self.checks {
+            if *level == Level::Off {
+                continue;
+            }
+            let mut reporter = Reporter {
+                ctx,
+                name: check.name(),
+                level: *level,
+                scribe,
+            };
+            check.check(ctx, &mut reporter);
+        }
+    }
+}
+
+/// Compile `source` and run `registry` over it, returning the line and message of each warning
+/// and error.
+#[cfg(test)]
+fn check_source(source: &str, registry: &Registry) -> Scribe {
+    let arena = crate::Arena::new();
+    let dts = crate::parse::parse_typed(source, &arena).unwrap();
+    let mut scribe = Scribe::new(false);
+    let (tree, node_labels, node_changes, prop_changes) = crate::merge::merge(dts, &mut scribe);
+    let loader = crate::fs::DummyLoader;
+    let binary = crate::eval::eval(tree, node_labels, &loader, &mut scribe);
+    let source = Source {
+        dts,
+        node_changes: &node_changes,
+        prop_changes: &prop_changes,
+    };
+    registry.run(&Context::new(&binary, Some(source)), &mut scribe);
+    scribe
+}
+
+#[cfg(test)]
+fn run_on_source(source: &str, registry: &Registry) -> (Vec<String>, Vec<String>) {
+    use pest::error::{ErrorVariant, LineColLocation};
+    let (warnings, errors) = check_source(source, registry).into_inner();
+    let summary = |v: Vec<SourceError>| -> Vec<String> {
+        v.iter()
+            .map(|e| {
+                let ErrorVariant::CustomError { message } = &e.pest_error.variant else {
+                    unreachable!()
+                };
+                let (LineColLocation::Pos((line, _)) | LineColLocation::Span((line, _), _)) =
+                    e.pest_error.line_col;
+                format!("{line}: {message}")
+            })
+            .collect()
+    };
+    (summary(warnings), summary(errors))
+}
+
+#[test]
+fn test_checks() {
+    let source = r#"
+/dts-v1/;
+/ {
+    #address-cells = <1 2>;
+    model = <1>;
+    n { status = "okay"; };
+};
+&{/n} { status = <0>; };
+"#;
+    let run = |registry: &Registry| run_on_source(source, registry);
+
+    let mut registry = Registry::default();
+    let (warnings, errors) = run(&registry);
+    assert_eq!(
+        warnings,
+        [
+            "5: (model_is_string) /model: property is not a string",
+            "8: (status_is_string) /n/status: property is not a string",
+            "4: (address_cells_is_cell) /#address-cells: property is not a single cell",
+        ]
+    );
+    assert!(errors.is_empty());
+
+    registry
+        .configure("no-model_is_string", Level::Warning)
+        .unwrap();
+    registry
+        .configure("status_is_string", Level::Error)
+        .unwrap();
+    let (warnings, errors) = run(&registry);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("status_is_string"));
+
+    registry
+        .configure("no-status_is_string", Level::Error)
+        .unwrap();
+    assert_eq!(registry.level("status_is_string"), Some(Level::Warning));
+    assert!(registry.configure("no-such_check", Level::Warning).is_err());
+
+    // -W enables a check without demoting it.
+    registry
+        .configure("node_name_format", Level::Warning)
+        .unwrap();
+    assert_eq!(registry.level("node_name_format"), Some(Level::Error));
+    registry.configure("unused_label", Level::Warning).unwrap();
+    assert_eq!(registry.level("unused_label"), Some(Level::Warning));
+}
+
+#[test]
+fn test_fixes() {
+    let source = r#"
+/dts-v1/;
+/ {
+    #address-cells = <1>;
+    #size-cells = <1>;
+    uart@0x1000 { reg = <0x1000 0x100>; };
+    spi@2000 {   reg = <0x3000 0x100>; };
+    gpio@4000 { reg = <0x5000 0x10>; };
+    timer@0x6000 { reg = <0x6000 0x100>; counter { }; };
+    user { target = <&{/timer@0x6000/counter}>; };
+    dma@0x7000 { reg = <0x8000 0x10>; };
+    serial@0x9000 { reg = <0x9000 0x10>; };
+    console@0xa000 { reg = <0xa000 0x10>; };
+    rtc@0xb000 { reg = <0xb000 0x10>; };
+    rtc@b000 { };
+    aliases { serial0 = "/serial@0x9000"; };
+    chosen { stdout-path = "/console@0xa000:115200n8"; };
+};
+&{/gpio@4000} { status = "okay"; };
+"#;
+    let scribe = check_source(source, &Registry::default());
+    let fixes = scribe.fixes();
+    let (warnings, _) = scribe.into_inner();
+    assert_eq!(warnings.len(), 10, "{warnings:?}");
+    let fixes: Vec<_> = fixes.iter().collect();
+    let (fixed, skipped) = crate::print::apply_edits(source, &fixes).unwrap();
+    // Both unit_address_format and unit_address_vs_reg would rename dma@0x7000.
+    assert_eq!(skipped, 1);
+    assert_eq!(
+        fixed,
+        source
+            .replace("uart@0x1000", "uart@1000")
+            .replace("spi@2000", "spi@3000")
+            .replace("dma@0x7000", "dma@7000")
+    );
+}
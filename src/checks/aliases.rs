@@ -0,0 +1,220 @@
+//! Checks of the `/aliases` and `/chosen` nodes, whose properties name other nodes by path.
+
+use super::{Check, Context, Registry, Reporter};
+use crate::BinaryNode;
+use crate::path::NodePath;
+
+pub(super) fn register(registry: &mut Registry) {
+    registry.register(Box::new(AliasPaths));
+    registry.register(Box::new(AliasDisabled));
+    registry.register(Box::new(ChosenNodeIsRoot));
+    registry.register(Box::new(ChosenNodeBootargs));
+    registry.register(Box::new(ChosenNodeStdoutPath));
+}
+
+/// Properties of `/aliases` which are not aliases.
+const NOT_ALIASES: &[&str] = &["phandle", "linux,phandle"];
+
+fn is_alias_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'
+}
+
+/// Call `f` with the path of `/aliases` and the name of each alias.
+fn for_each_alias(ctx: &Context, mut f: impl FnMut(&NodePath, &str)) {
+    let path = NodePath::root().join("aliases");
+    let Some(aliases) = ctx.tree.get_child("aliases") else {
+        return;
+    };
+    for (name, _) in aliases.properties() {
+        if !NOT_ALIASES.contains(&name.as_str()) {
+            f(&path, name);
+        }
+    }
+}
+
+/// Resolve the value of an alias, which must be the absolute path of a node.
+fn resolve_alias(ctx: &Context, aliases: &BinaryNode, name: &str) -> Result<NodePath, String> {
+    let Some(value) = aliases.get_string(name) else {
+        return Err("alias is not a string".into());
+    };
+    if !value.starts_with('/') {
+        return Err(format!("alias \"{value}\" is not an absolute path"));
+    }
+    ctx.resolve_path(value)
+        .ok_or_else(|| format!("alias \"{value}\" does not name a node"))
+}
+
+/// Each alias must name a node, and alias names are lowercase letters, digits, and '-'.
+struct AliasPaths;
+
+impl Check for AliasPaths {
+    fn name(&self) -> &'static str {
+        "alias_paths"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let Some(aliases) = ctx.tree.get_child("aliases") else {
+            return;
+        };
+        for_each_alias(ctx, |path, name| {
+            if !name.chars().all(is_alias_char) {
+                let message = "alias names may contain only lowercase letters, digits, and '-'";
+                report.property_name(path, name, message);
+            }
+            if let Err(message) = resolve_alias(ctx, aliases, name) {
+                report.property(path, name, message);
+            }
+        });
+    }
+}
+
+/// An alias usually names a device the bootloader or OS will use, so it should not be disabled.
+struct AliasDisabled;
+
+impl Check for AliasDisabled {
+    fn name(&self) -> &'static str {
+        "alias_disabled"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let Some(aliases) = ctx.tree.get_child("aliases") else {
+            return;
+        };
+        for_each_alias(ctx, |path, name| {
+            let Ok(target) = resolve_alias(ctx, aliases, name) else {
+                return;
+            };
+            let node = ctx.tree.walk(target.segments()).unwrap();
+            if node.get_string("status") == Some("disabled") {
+                report.property(
+                    path,
+                    name,
+                    format!("alias refers to disabled node {target}"),
+                );
+            }
+        });
+    }
+}
+
+struct ChosenNodeIsRoot;
+
+impl Check for ChosenNodeIsRoot {
+    fn name(&self) -> &'static str {
+        "chosen_node_is_root"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, _) in ctx.nodes() {
+            if !path.is_root() && path.leaf() == "chosen" && !path.parent().is_root() {
+                report.node(&path, "chosen node must be a child of the root node");
+            }
+        }
+    }
+}
+
+struct ChosenNodeBootargs;
+
+impl Check for ChosenNodeBootargs {
+    fn name(&self) -> &'static str {
+        "chosen_node_bootargs"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let path = NodePath::root().join("chosen");
+        let Some(chosen) = ctx.tree.get_child("chosen") else {
+            return;
+        };
+        if chosen.get_property("bootargs").is_some() && chosen.get_string("bootargs").is_none() {
+            report.property(&path, "bootargs", "property is not a string");
+        }
+    }
+}
+
+/// `stdout-path` names the console device, by its path or an alias, optionally followed by ':'
+/// and device-specific options such as the baud rate.
+struct ChosenNodeStdoutPath;
+
+impl Check for ChosenNodeStdoutPath {
+    fn name(&self) -> &'static str {
+        "chosen_node_stdout_path"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let path = NodePath::root().join("chosen");
+        let Some(chosen) = ctx.tree.get_child("chosen") else {
+            return;
+        };
+        if chosen.get_property("linux,stdout-path").is_some() {
+            let message = "use \"stdout-path\" instead";
+            report.property_name(&path, "linux,stdout-path", message);
+        }
+        for name in ["stdout-path", "linux,stdout-path"] {
+            if chosen.get_property(name).is_none() {
+                continue;
+            }
+            let Some(value) = chosen.get_string(name) else {
+                report.property(&path, name, "property is not a string");
+                continue;
+            };
+            let (device, _options) = value.split_once(':').unwrap_or((value, ""));
+            let resolved = match device.starts_with('/') {
+                true => ctx
+                    .resolve_path(device)
+                    .ok_or_else(|| format!("\"{device}\" does not name a node")),
+                false => match ctx.tree.get_child("aliases") {
+                    Some(aliases) if aliases.get_property(device).is_some() => {
+                        resolve_alias(ctx, aliases, device)
+                            .map_err(|_| format!("alias \"{device}\" is invalid"))
+                    }
+                    _ => Err(format!("\"{device}\" is neither a path nor an alias")),
+                },
+            };
+            if let Err(message) = resolved {
+                report.property(&path, name, message);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_aliases() {
+    let source = r#"
+/dts-v1/;
+/ {
+    aliases {
+        serial0 = "/uart@1000";
+        serial1 = &uart1;
+        Serial_2 = "/uart@1000";
+        spi0 = "/spi";
+        gpio0 = "uart@1000";
+        i2c0 = <1>;
+    };
+    chosen {
+        bootargs = <0>;
+        stdout-path = "serial0:115200n8";
+        linux,stdout-path = "/uart@3000";
+    };
+    uart@1000 { };
+    uart1: uart@2000 { status = "disabled"; };
+    soc { chosen { }; };
+};
+"#;
+    let mut registry = Registry::empty();
+    register(&mut registry);
+    let (warnings, errors) = super::run_on_source(source, &registry);
+    assert_eq!(
+        warnings,
+        [
+            "7: (alias_paths) /aliases/Serial_2: alias names may contain only lowercase letters, digits, and '-'",
+            "8: (alias_paths) /aliases/spi0: alias \"/spi\" does not name a node",
+            "9: (alias_paths) /aliases/gpio0: alias \"uart@1000\" is not an absolute path",
+            "10: (alias_paths) /aliases/i2c0: alias is not a string",
+            "6: (alias_disabled) /aliases/serial1: alias refers to disabled node /uart@2000",
+            "19: (chosen_node_is_root) /soc/chosen: chosen node must be a child of the root node",
+            "13: (chosen_node_bootargs) /chosen/bootargs: property is not a string",
+            "15: (chosen_node_stdout_path) /chosen/linux,stdout-path: use \"stdout-path\" instead",
+            "15: (chosen_node_stdout_path) /chosen/linux,stdout-path: \"/uart@3000\" does not name a node",
+        ]
+    );
+    assert!(errors.is_empty());
+}
@@ -0,0 +1,382 @@
+//! Checks of the conventions of particular buses.  A bus is recognized by its `device_type`,
+//! `compatible`, or node name, and its children are checked against the bus's addressing.
+//!
+//! Unit addresses are compared with `reg` by `unit_address_vs_reg`, except on PCI.
+
+use super::{Check, Context, Registry, Reporter};
+use crate::BinaryNode;
+use crate::graph::base_name;
+use crate::path::NodePath;
+
+pub(super) fn register(registry: &mut Registry) {
+    registry.register(Box::new(Bridge(Bus::Pci)));
+    registry.register(Box::new(PciDeviceReg));
+    registry.register(Box::new(PciDeviceBusNum));
+    registry.register(Box::new(Bridge(Bus::Simple)));
+    registry.register(Box::new(SimpleBusReg));
+    registry.register(Box::new(Bridge(Bus::I2c)));
+    registry.register(Box::new(I2cBusReg));
+    registry.register(Box::new(Bridge(Bus::Spi)));
+    registry.register(Box::new(SpiBusReg));
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Bus {
+    Pci,
+    Simple,
+    I2c,
+    Spi,
+}
+
+/// Whether some `compatible` string names a device of the given class, as in "vendor,soc-i2c".
+fn compatible_class(node: &BinaryNode, class: &str) -> bool {
+    let strings = node.get_strings("compatible").unwrap_or_default();
+    strings.iter().any(|s| {
+        let model = s.rsplit(',').next().unwrap();
+        model == class || model.strip_suffix(class).is_some_and(|m| m.ends_with('-'))
+    })
+}
+
+impl Bus {
+    fn of(path: &NodePath, node: &BinaryNode) -> Option<Bus> {
+        let name = base_name(path);
+        if node.get_string("device_type") == Some("pci") {
+            Some(Bus::Pci)
+        } else if node
+            .get_strings("compatible")
+            .is_some_and(|c| c.contains(&"simple-bus"))
+        {
+            Some(Bus::Simple)
+        } else if ["i2c", "i2c-bus", "i2c-arb"].contains(&name) || compatible_class(node, "i2c") {
+            Some(Bus::I2c)
+        } else if name == "spi" || compatible_class(node, "spi") {
+            Some(Bus::Spi)
+        } else {
+            None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Bus::Pci => "PCI",
+            Bus::Simple => "simple-bus",
+            Bus::I2c => "I2C",
+            Bus::Spi => "SPI",
+        }
+    }
+}
+
+/// Call `f` with each child of a bus of kind `bus`, and its parent.
+fn for_each_child(ctx: &Context, bus: Bus, mut f: impl FnMut(&NodePath, &BinaryNode, &BinaryNode)) {
+    for (path, node) in ctx.nodes() {
+        if Bus::of(&path, node) != Some(bus) {
+            continue;
+        }
+        for (name, child) in node.children() {
+            f(&path.join(name), child, node);
+        }
+    }
+}
+
+/// The bus node itself must have the expected cell sizes and properties.
+struct Bridge(Bus);
+
+impl Check for Bridge {
+    fn name(&self) -> &'static str {
+        match self.0 {
+            Bus::Pci => "pci_bridge",
+            Bus::Simple => "simple_bus_bridge",
+            Bus::I2c => "i2c_bus_bridge",
+            Bus::Spi => "spi_bus_bridge",
+        }
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let bus = self.0;
+        for (path, node) in ctx.nodes() {
+            if Bus::of(&path, node) != Some(bus) {
+                continue;
+            }
+            // A SPI controller acting as a device has no addressable children.
+            let spi_slave = node.get_property("spi-slave").is_some();
+            let (address_cells, size_cells) = match bus {
+                Bus::Pci => (Some(3), Some(2)),
+                Bus::Simple => (None, None),
+                Bus::I2c => (Some(1), Some(0)),
+                Bus::Spi if spi_slave => (Some(0), Some(0)),
+                Bus::Spi => (Some(1), Some(0)),
+            };
+            // Controllers without children, as often declared by SoC include files, may
+            // leave the cell sizes to the board.  PCI bridges are always checked.
+            let name = bus.name();
+            for (property, expected) in [
+                ("#address-cells", address_cells),
+                ("#size-cells", size_cells),
+            ] {
+                if let Some(expected) = expected
+                    && node.get_u32(property) != Some(expected)
+                    && (node.children().next().is_some() || bus == Bus::Pci)
+                {
+                    let message = format!("{name} bus should have {property} = <{expected}>");
+                    report.node(&path, message);
+                }
+            }
+            match bus {
+                Bus::Pci => check_pci_bridge(&path, node, report),
+                Bus::Simple if node.get_property("ranges").is_none() => {
+                    report.node(&path, "simple-bus has no ranges property");
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+fn check_pci_bridge(path: &NodePath, node: &BinaryNode, report: &mut Reporter) {
+    if !["pci", "pcie"].contains(&base_name(path)) {
+        report.node(path, "PCI bridge node name should be \"pci\" or \"pcie\"");
+    }
+    if node.get_property("ranges").is_none() {
+        report.node(path, "PCI bridge has no ranges property");
+    }
+    if node.get_property("bus-range").is_none() {
+        return;
+    }
+    match node.get_cells("bus-range").as_deref() {
+        Some(&[_, max]) if max > 255 => {
+            let message = format!("maximum bus number {max} exceeds 255");
+            report.property(path, "bus-range", message);
+        }
+        Some(&[min, max]) if min > max => {
+            let message = format!("minimum bus number {min} exceeds maximum {max}");
+            report.property(path, "bus-range", message);
+        }
+        Some(&[_, _]) => (),
+        _ => report.property(path, "bus-range", "bus-range should be two cells"),
+    }
+}
+
+/// The first cell of a PCI address, phys.hi, has the form `npt000ss bbbbbbbb dddddfff rrrrrrrr`:
+/// flags, space code, bus, device, function, and register.  A device's `reg` begins with its
+/// configuration space, whose register number must be zero.
+struct PciDeviceReg;
+
+impl Check for PciDeviceReg {
+    fn name(&self) -> &'static str {
+        "pci_device_reg"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for_each_child(ctx, Bus::Pci, |path, child, _| {
+            let Some(reg) = child.get_cells("reg") else {
+                return;
+            };
+            let [hi, mid, lo, ..] = reg[..] else {
+                report.property(path, "reg", "PCI reg is shorter than an address");
+                return;
+            };
+            if mid != 0 || lo != 0 {
+                let message = "PCI reg config space address cells 2 and 3 must be 0";
+                report.property(path, "reg", message);
+            }
+            if hi & 0xff000000 != 0 {
+                report.property(path, "reg", "PCI reg address is not configuration space");
+            }
+            if hi & 0xff != 0 {
+                let message = "PCI reg config space address register number must be 0";
+                report.property(path, "reg", message);
+            }
+            let device = (hi >> 11) & 0x1f;
+            let function = (hi >> 8) & 0x7;
+            let expected = match function {
+                0 => format!("{device:x}"),
+                _ => format!("{device:x},{function:x}"),
+            };
+            let unit_address = path.leaf().split_once('@').map(|(_, a)| a);
+            if unit_address != Some(expected.as_str()) {
+                let message = format!("PCI unit address format error, expected \"{expected}\"");
+                report.node(path, message);
+            }
+        });
+    }
+}
+
+/// A device's bus number must lie within its bridge's `bus-range`.
+struct PciDeviceBusNum;
+
+impl Check for PciDeviceBusNum {
+    fn name(&self) -> &'static str {
+        "pci_device_bus_num"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for_each_child(ctx, Bus::Pci, |path, child, parent| {
+            let (Some(&[min, max]), Some(&hi)) = (
+                parent.get_cells("bus-range").as_deref(),
+                child.get_cells("reg").as_deref().and_then(|r| r.first()),
+            ) else {
+                return;
+            };
+            let bus = (hi >> 16) & 0xff;
+            if !(min..=max).contains(&bus) {
+                let message = format!("PCI bus number {bus} out of range, expected {min}-{max}");
+                report.property(path, "reg", message);
+            }
+        });
+    }
+}
+
+/// Children of a simple-bus are memory-mapped devices or further buses.
+struct SimpleBusReg;
+
+impl Check for SimpleBusReg {
+    fn name(&self) -> &'static str {
+        "simple_bus_reg"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for_each_child(ctx, Bus::Simple, |path, child, _| {
+            if child.get_property("reg").is_none() && child.get_property("ranges").is_none() {
+                report.node(path, "simple-bus child has no reg or ranges property");
+            }
+        });
+    }
+}
+
+/// Each I2C address has 7 bits, or 10 if flagged so.
+struct I2cBusReg;
+
+const I2C_TEN_BIT_ADDRESS: u32 = 1 << 31;
+const I2C_OWN_SLAVE_ADDRESS: u32 = 1 << 30;
+
+impl Check for I2cBusReg {
+    fn name(&self) -> &'static str {
+        "i2c_bus_reg"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for_each_child(ctx, Bus::I2c, |path, child, _| {
+            if child.get_property("reg").is_none() {
+                // An I2C bus may contain nodes which are not devices, such as a mux's channels.
+                return;
+            }
+            let Some(reg) = child.get_cells("reg").filter(|r| !r.is_empty()) else {
+                report.property(path, "reg", "I2C reg should be a list of addresses");
+                return;
+            };
+            for cell in reg {
+                let address = cell & !(I2C_TEN_BIT_ADDRESS | I2C_OWN_SLAVE_ADDRESS);
+                if cell & I2C_TEN_BIT_ADDRESS != 0 {
+                    if address > 0x3ff {
+                        let message =
+                            format!("I2C address must be less than 10 bits, got {address:#x}");
+                        report.property(path, "reg", message);
+                    }
+                } else if address > 0x7f {
+                    let message = format!(
+                        "I2C address must be less than 7 bits, got {address:#x}.  Set \
+                         I2C_TEN_BIT_ADDRESS for 10-bit addresses or fix the property"
+                    );
+                    report.property(path, "reg", message);
+                }
+            }
+        });
+    }
+}
+
+/// Each SPI device's `reg` lists its chip selects, which must exist on the controller.
+struct SpiBusReg;
+
+impl Check for SpiBusReg {
+    fn name(&self) -> &'static str {
+        "spi_bus_reg"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for_each_child(ctx, Bus::Spi, |path, child, parent| {
+            if parent.get_property("spi-slave").is_some() {
+                return;
+            }
+            let Some(reg) = child.get_cells("reg") else {
+                report.node(path, "SPI device has no reg property");
+                return;
+            };
+            let Some(num_cs) = parent.get_u32("num-cs") else {
+                return;
+            };
+            for cs in reg {
+                if cs >= num_cs {
+                    let message =
+                        format!("chip select {cs} is out of range; the controller has {num_cs}");
+                    report.property(path, "reg", message);
+                }
+            }
+        });
+    }
+}
+
+#[test]
+fn test_buses() {
+    let source = r#"
+/dts-v1/;
+/ {
+    #address-cells = <1>;
+    #size-cells = <1>;
+    soc {
+        compatible = "simple-bus";
+        #address-cells = <1>;
+        #size-cells = <1>;
+        ranges;
+        uart@1000 { reg = <0x1000 0x100>; };
+        stray { };
+
+        i2c@2000 {
+            compatible = "acme,soc-i2c";
+            reg = <0x2000 0x100>;
+            #address-cells = <1>;
+            #size-cells = <0>;
+            eeprom@50 { reg = <0x50>; };
+            wide@3ff { reg = <0x800003ff>; };
+            bad@1ff { reg = <0x1ff>; };
+        };
+
+        spi@3000 {
+            compatible = "acme,soc-spi";
+            reg = <0x3000 0x100>;
+            #address-cells = <1>;
+            num-cs = <2>;
+            flash@0 { reg = <0>; };
+            adc@2 { reg = <2>; };
+        };
+    };
+
+    pcie@10000000 {
+        device_type = "pci";
+        reg = <0x10000000 0x1000>;
+        #address-cells = <3>;
+        #size-cells = <2>;
+        ranges;
+        bus-range = <0 1>;
+        ethernet@1 { reg = <0x0800 0 0 0 0>; };
+        nic@2,1 { reg = <0x21100 0 0 0 0>; };
+        wrong@5 { reg = <0x1804 0 0 0 0>; };
+    };
+};
+"#;
+    let mut registry = Registry::empty();
+    register(&mut registry);
+    let (warnings, errors) = super::run_on_source(source, &registry);
+    assert_eq!(
+        warnings,
+        [
+            "43: (pci_device_reg) /pcie@10000000/wrong@5/reg: PCI reg config space address register number must be 0",
+            "43: (pci_device_reg) /pcie@10000000/wrong@5: PCI unit address format error, expected \"3\"",
+            "42: (pci_device_bus_num) /pcie@10000000/nic@2,1/reg: PCI bus number 2 out of range, expected 0-1",
+            "12: (simple_bus_reg) /soc/stray: simple-bus child has no reg or ranges property",
+            "21: (i2c_bus_reg) /soc/i2c@2000/bad@1ff/reg: I2C address must be less than 7 bits, got 0x1ff.  Set I2C_TEN_BIT_ADDRESS for 10-bit addresses or fix the property",
+            "24: (spi_bus_bridge) /soc/spi@3000: SPI bus should have #size-cells = <0>",
+            "30: (spi_bus_reg) /soc/spi@3000/adc@2/reg: chip select 2 is out of range; the controller has 2",
+        ]
+    );
+    assert!(errors.is_empty());
+}
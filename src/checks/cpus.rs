@@ -0,0 +1,399 @@
+//! Checks of the CPU and memory topology: `/cpus`, its `cpu-map`, and `/memory` nodes, along
+//! with reserved memory.
+
+use super::{Check, Context, Registry, Reporter};
+use crate::BinaryNode;
+use crate::address::Region;
+use crate::graph::base_name;
+use crate::path::NodePath;
+use pest::Span;
+
+pub(super) fn register(registry: &mut Registry) {
+    registry.register(Box::new(CpusCells));
+    registry.register(Box::new(CpuNode));
+    registry.register(Box::new(CpuEnableMethod));
+    registry.register(Box::new(CpuMap));
+    registry.register(Box::new(MemoryNode));
+    registry.register(Box::new(MemoryOverlap));
+}
+
+/// Call `f` with the path of each `cpu` node under `/cpus`.
+fn for_each_cpu(ctx: &Context, mut f: impl FnMut(&NodePath, &BinaryNode, &BinaryNode)) {
+    let path = NodePath::root().join("cpus");
+    let Some(cpus) = ctx.tree.get_child("cpus") else {
+        return;
+    };
+    for (name, node) in cpus.children() {
+        let cpu = path.join(name);
+        if base_name(&cpu) == "cpu" {
+            f(&cpu, node, cpus);
+        }
+    }
+}
+
+/// `/cpus` addresses its children by hardware thread id, which has no size.
+struct CpusCells;
+
+impl Check for CpusCells {
+    fn name(&self) -> &'static str {
+        "cpus_cells"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let path = NodePath::root().join("cpus");
+        let Some(cpus) = ctx.tree.get_child("cpus") else {
+            return;
+        };
+        match cpus.get_u32("#address-cells") {
+            Some(1 | 2) => (),
+            Some(_) => report.property(&path, "#address-cells", "#address-cells must be 1 or 2"),
+            None => report.node(&path, "missing #address-cells (should be 1 or 2)"),
+        }
+        match cpus.get_u32("#size-cells") {
+            Some(0) => (),
+            Some(_) => report.property(&path, "#size-cells", "#size-cells must be 0"),
+            None => report.node(&path, "missing #size-cells (should be 0)"),
+        }
+    }
+}
+
+/// Each `cpu` node has `device_type = "cpu"` and a `reg` listing its thread ids.  Agreement
+/// between `reg` and the unit address is checked by unit_address_vs_reg.
+struct CpuNode;
+
+impl Check for CpuNode {
+    fn name(&self) -> &'static str {
+        "cpu_node"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for_each_cpu(ctx, |path, node, cpus| {
+            match node.get_string("device_type") {
+                Some("cpu") => (),
+                Some(_) => report.property(path, "device_type", "device_type must be \"cpu\""),
+                None => report.node(path, "missing device_type = \"cpu\""),
+            }
+            let Some(reg) = node.get_property("reg") else {
+                report.node(path, "cpu node has no reg property");
+                return;
+            };
+            let ac = cpus.address_cells() as usize;
+            if reg.is_empty() || ac == 0 || !reg.len().is_multiple_of(4 * ac) {
+                let n = reg.len();
+                let message =
+                    format!("reg property has invalid length ({n} bytes) (#address-cells == {ac})");
+                report.property(path, "reg", message);
+            }
+        });
+    }
+}
+
+/// Enable methods which need more information to start a secondary cpu.
+struct CpuEnableMethod;
+
+impl Check for CpuEnableMethod {
+    fn name(&self) -> &'static str {
+        "cpu_enable_method"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let has_psci = ctx.tree.children().any(|(name, node)| {
+            name.split('@').next() == Some("psci")
+                || node
+                    .get_strings("compatible")
+                    .is_some_and(|c| c.iter().any(|c| c.starts_with("arm,psci")))
+        });
+        for_each_cpu(ctx, |path, node, _| {
+            if node.get_property("enable-method").is_none() {
+                return;
+            }
+            let Some(methods) = node.get_strings("enable-method") else {
+                report.property(path, "enable-method", "property is not a string list");
+                return;
+            };
+            for method in methods {
+                match method {
+                    "spin-table" => match node.get_property("cpu-release-addr") {
+                        None => {
+                            let message = "enable-method \"spin-table\" requires cpu-release-addr";
+                            report.property(path, "enable-method", message);
+                        }
+                        Some(addr) if addr.len() != 8 => {
+                            let message = "cpu-release-addr must be a 64-bit address";
+                            report.property(path, "cpu-release-addr", message);
+                        }
+                        Some(_) => (),
+                    },
+                    "psci" if !has_psci => {
+                        let message = "enable-method \"psci\" requires a /psci node";
+                        report.property(path, "enable-method", message);
+                    }
+                    _ => (),
+                }
+            }
+        });
+    }
+}
+
+/// `cpu-map` nests socket, cluster, core, and thread nodes, whose leaves name cpu nodes.
+struct CpuMap;
+
+impl CpuMap {
+    fn check_node(ctx: &Context, report: &mut Reporter, path: &NodePath, node: &BinaryNode) {
+        let mut leaf = true;
+        for (name, child) in node.children() {
+            let child_path = path.join(name);
+            leaf = false;
+            let valid = ["socket", "cluster", "core", "thread"]
+                .iter()
+                .any(|prefix| {
+                    name.strip_prefix(prefix)
+                        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+                });
+            if !valid {
+                let message = "cpu-map nodes must be named socketN, clusterN, coreN, or threadN";
+                report.node(&child_path, message);
+                continue;
+            }
+            Self::check_node(ctx, report, &child_path, child);
+        }
+        if path.leaf() == "cpu-map" {
+            return;
+        }
+        let has_cpu = node.get_property("cpu").is_some();
+        if !leaf {
+            if has_cpu {
+                report.property(
+                    path,
+                    "cpu",
+                    "only leaf nodes of cpu-map may have a cpu property",
+                );
+            }
+            return;
+        }
+        if !has_cpu {
+            report.node(path, "cpu-map leaf node has no cpu property");
+            return;
+        }
+        let cpus = NodePath::root().join("cpus");
+        let target = node
+            .get_u32("cpu")
+            .and_then(|phandle| ctx.phandle_target(phandle));
+        match target {
+            None => report.property(path, "cpu", "cpu property does not refer to a node"),
+            Some((target, node))
+                if target.parent() != cpus || node.get_string("device_type") != Some("cpu") =>
+            {
+                let message = format!("cpu property refers to {target}, which is not a cpu node");
+                report.property(path, "cpu", message);
+            }
+            Some(_) => (),
+        }
+    }
+}
+
+impl Check for CpuMap {
+    fn name(&self) -> &'static str {
+        "cpu_map"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let path = NodePath::root().join("cpus").join("cpu-map");
+        if let Some(map) = ctx.tree.walk(path.segments()) {
+            Self::check_node(ctx, report, &path, map);
+        }
+    }
+}
+
+/// Each `/memory` node has `device_type = "memory"` and a `reg` of nonempty regions.  The
+/// length of `reg` is checked by reg_format.
+struct MemoryNode;
+
+impl Check for MemoryNode {
+    fn name(&self) -> &'static str {
+        "memory_node"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let (ac, sc) = (
+            ctx.tree.address_cells() as usize,
+            ctx.tree.size_cells() as usize,
+        );
+        for (name, node) in ctx.tree.children() {
+            let path = NodePath::root().join(name);
+            if base_name(&path) != "memory" {
+                continue;
+            }
+            match node.get_string("device_type") {
+                Some("memory") => (),
+                Some(_) => report.property(&path, "device_type", "device_type must be \"memory\""),
+                None => report.node(&path, "missing device_type = \"memory\""),
+            }
+            let Some(reg) = node.get_cells("reg") else {
+                report.node(&path, "memory node has no reg property");
+                continue;
+            };
+            if !(1..=2).contains(&ac) || !(1..=2).contains(&sc) {
+                continue;
+            }
+            if reg.is_empty() || !reg.len().is_multiple_of(ac + sc) {
+                continue;
+            }
+            for entry in reg.chunks(ac + sc) {
+                let size = entry[ac..].iter().fold(0u64, |n, &c| (n << 32) | c as u64);
+                if size == 0 {
+                    let address = entry[..ac].iter().fold(0u64, |n, &c| (n << 32) | c as u64);
+                    let message = format!("memory region at 0x{address:x} has zero size");
+                    report.property(&path, "reg", message);
+                }
+            }
+        }
+    }
+}
+
+/// The `/memory` nodes do not overlap one another, and neither do reserved regions, whether
+/// children of `/reserved-memory` or `/memreserve/` entries.  (Reserved regions lie within
+/// memory, of course.)
+struct MemoryOverlap;
+
+impl Check for MemoryOverlap {
+    fn name(&self) -> &'static str {
+        "memory_overlap"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        struct Declared<'a> {
+            /// Whether this is a `/memory` node, rather than reserved memory.
+            memory: bool,
+            region: Region,
+            path: NodePath,
+            memreserve: Option<Span<'a>>,
+        }
+        let mut regions = vec![];
+        let mut add_node = |memory, path: NodePath| {
+            for region in crate::address::reg(ctx.tree, &path).unwrap_or_default() {
+                let path = path.clone();
+                let memreserve = None;
+                regions.push(Declared {
+                    memory,
+                    region,
+                    path,
+                    memreserve,
+                });
+            }
+        };
+        for (name, _) in ctx.tree.children() {
+            if name.split('@').next() == Some("memory") {
+                add_node(true, NodePath::root().join(name));
+            }
+        }
+        if let Some(reserved) = ctx.tree.get_child("reserved-memory") {
+            let reserved_path = NodePath::root().join("reserved-memory");
+            for (name, _) in reserved.children() {
+                add_node(false, reserved_path.join(name));
+            }
+        }
+        let memreserves = ctx.source.as_ref().map(|s| crate::eval::memreserves(s.dts));
+        for (span, address, size) in memreserves.and_then(Result::ok).unwrap_or_default() {
+            regions.push(Declared {
+                memory: false,
+                region: Region {
+                    address: address.into(),
+                    size: size.into(),
+                },
+                path: NodePath::root(),
+                memreserve: Some(span),
+            });
+        }
+        regions.retain(|r| r.region.size != 0);
+        regions.sort_by_key(|r| (r.region.address, r.region.size));
+        let describe = |r: &Declared| match r.memreserve {
+            Some(_) => format!("/memreserve/ {:#x} {:#x}", r.region.address, r.region.size),
+            None => r.path.to_string(),
+        };
+        for (i, a) in regions.iter().enumerate() {
+            let end = a.region.address.saturating_add(a.region.size);
+            for b in regions[i + 1..]
+                .iter()
+                .take_while(|b| b.region.address < end)
+            {
+                if a.memory != b.memory || (a.memreserve.is_none() && a.path == b.path) {
+                    continue;
+                }
+                let Region { address, size } = b.region;
+                let message = format!(
+                    "region {address:#x} (size {size:#x}) overlaps {}",
+                    describe(a)
+                );
+                match b.memreserve {
+                    Some(span) => report.at(Some(span), &b.path, message),
+                    None => report.property(&b.path, "reg", message),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_cpus() {
+    let source = r#"
+/dts-v1/;
+/ {
+    #address-cells = <2>;
+    #size-cells = <2>;
+    cpus {
+        #address-cells = <1>;
+        #size-cells = <1>;
+        cpu0: cpu@0 { device_type = "cpu"; reg = <0>; enable-method = "psci"; };
+        cpu1: cpu@1 { device_type = "cpu"; reg = <1>; enable-method = "spin-table"; };
+        cpu@2 { reg = <2>; enable-method = "spin-table"; cpu-release-addr = <0x8000>; };
+        cpu@3 { device_type = "cpu"; };
+        cpu-map {
+            cluster0 {
+                core0 { cpu = <&cpu0>; };
+                core1 { cpu = <&mem>; };
+                core2 { };
+                cpu1 { cpu = <&cpu1>; };
+            };
+            cluster1 { cpu2 { cpu = <&cpu1>; }; };
+        };
+    };
+    mem: memory@80000000 { device_type = "memory"; reg = <0 0x80000000 0 0x1000 0 0 0 0>; };
+    memory@0 { };
+    memory@80000800 { device_type = "memory"; reg = <0 0x80000800 0 0x1000>; };
+    reserved-memory {
+        #address-cells = <2>;
+        #size-cells = <2>;
+        ranges;
+        fw@80000000 { reg = <0 0x80000000 0 0x400>; };
+        shm@80000200 { reg = <0 0x80000200 0 0x100>; };
+    };
+};
+/memreserve/ 0x80000300 0x10;
+"#;
+    let mut registry = Registry::empty();
+    register(&mut registry);
+    let (warnings, errors) = super::run_on_source(source, &registry);
+    assert_eq!(
+        warnings,
+        [
+            "8: (cpus_cells) /cpus/#size-cells: #size-cells must be 0",
+            "11: (cpu_node) /cpus/cpu@2: missing device_type = \"cpu\"",
+            "12: (cpu_node) /cpus/cpu@3: cpu node has no reg property",
+            "9: (cpu_enable_method) /cpus/cpu@0/enable-method: enable-method \"psci\" requires a /psci node",
+            "10: (cpu_enable_method) /cpus/cpu@1/enable-method: enable-method \"spin-table\" requires cpu-release-addr",
+            "11: (cpu_enable_method) /cpus/cpu@2/cpu-release-addr: cpu-release-addr must be a 64-bit address",
+            "16: (cpu_map) /cpus/cpu-map/cluster0/core1/cpu: cpu property refers to /memory@80000000, which is not a cpu node",
+            "17: (cpu_map) /cpus/cpu-map/cluster0/core2: cpu-map leaf node has no cpu property",
+            "18: (cpu_map) /cpus/cpu-map/cluster0/cpu1: cpu-map nodes must be named socketN, clusterN, coreN, or threadN",
+            "20: (cpu_map) /cpus/cpu-map/cluster1/cpu2: cpu-map nodes must be named socketN, clusterN, coreN, or threadN",
+            "23: (memory_node) /memory@80000000/reg: memory region at 0x0 has zero size",
+            "24: (memory_node) /memory@0: missing device_type = \"memory\"",
+            "24: (memory_node) /memory@0: memory node has no reg property",
+            "31: (memory_overlap) /reserved-memory/shm@80000200/reg: region 0x80000200 (size 0x100) overlaps /reserved-memory/fw@80000000",
+            "34: (memory_overlap) /: region 0x80000300 (size 0x10) overlaps /reserved-memory/fw@80000000",
+            "25: (memory_overlap) /memory@80000800/reg: region 0x80000800 (size 0x1000) overlaps /memory@80000000",
+        ]
+    );
+    assert!(errors.is_empty());
+}
@@ -0,0 +1,160 @@
+//! Checks of the OF graph described in `crate::graph`.
+
+use super::{Check, Context, Registry, Reporter};
+use crate::BinaryNode;
+use crate::graph::{Remote, base_name, endpoints};
+use crate::path::NodePath;
+
+pub(super) fn register(registry: &mut Registry) {
+    registry.register(Box::new(GraphChildAddress));
+    registry.register(Box::new(GraphPort));
+    registry.register(Box::new(GraphEndpoint));
+}
+
+/// A port or endpoint has a unit address if and only if it has `reg`, and they agree.
+fn check_graph_reg(path: &NodePath, node: &BinaryNode, report: &mut Reporter) {
+    let unit_address = path.leaf().split_once('@').map(|(_, a)| a);
+    match (unit_address, node.get_property("reg")) {
+        (None, None) => (),
+        (Some(_), None) => report.node(path, "graph node has a unit address, but no reg property"),
+        (None, Some(_)) => report.node(path, "graph node has a reg property, but no unit address"),
+        (Some(unit_address), Some(_)) => match node.get_u32("reg") {
+            Some(reg) if format!("{reg:x}") == unit_address => (),
+            Some(reg) => {
+                let message = format!("graph node unit address error, expected \"{reg:x}\"");
+                report.node(path, message);
+            }
+            None => report.property(path, "reg", "graph node reg should be a single cell"),
+        },
+    }
+}
+
+/// Nodes holding numbered ports or endpoints address them with one cell and no size.
+struct GraphChildAddress;
+
+impl Check for GraphChildAddress {
+    fn name(&self) -> &'static str {
+        "graph_child_address"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            let child = match base_name(&path) {
+                "ports" => "port",
+                "port" => "endpoint",
+                _ => continue,
+            };
+            let numbered = node
+                .children()
+                .any(|(name, _)| name.starts_with(&format!("{child}@")));
+            if !numbered {
+                continue;
+            }
+            if node.get_u32("#address-cells") != Some(1) {
+                report.node(&path, "graph node should have #address-cells = <1>");
+            }
+            if node.get_u32("#size-cells") != Some(0) {
+                report.node(&path, "graph node should have #size-cells = <0>");
+            }
+        }
+    }
+}
+
+struct GraphPort;
+
+impl Check for GraphPort {
+    fn name(&self) -> &'static str {
+        "graph_port"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            if !path.is_root() && base_name(&path) == "port" {
+                check_graph_reg(&path, node, report);
+            }
+        }
+    }
+}
+
+/// Endpoints belong to ports, and are connected in pairs which name each other.
+struct GraphEndpoint;
+
+impl Check for GraphEndpoint {
+    fn name(&self) -> &'static str {
+        "graph_endpoint"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            if path.is_root() || base_name(&path) != "endpoint" {
+                continue;
+            }
+            if base_name(&path.parent()) != "port" {
+                report.node(&path, "graph endpoint's parent should be a port");
+            }
+            check_graph_reg(&path, node, report);
+        }
+        let endpoints = endpoints(ctx.tree);
+        for endpoint in &endpoints {
+            let path = &endpoint.path;
+            let remote = match &endpoint.remote {
+                None => {
+                    report.node(path, "graph endpoint has no remote-endpoint");
+                    continue;
+                }
+                Some(Remote::Invalid) => {
+                    let message = "remote-endpoint is not a valid phandle";
+                    report.property(path, "remote-endpoint", message);
+                    continue;
+                }
+                Some(Remote::Endpoint(remote)) => remote,
+            };
+            let Some(back) = endpoints.iter().find(|e| e.path == *remote) else {
+                let message = format!("remote-endpoint {remote} is not an endpoint");
+                report.property(path, "remote-endpoint", message);
+                continue;
+            };
+            if !matches!(&back.remote, Some(Remote::Endpoint(p)) if p == path) {
+                let message = format!("graph connection to {remote} is not bidirectional");
+                report.property(path, "remote-endpoint", message);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_graph() {
+    let source = r#"
+/dts-v1/;
+/ {
+    dsi {
+        ports {
+            #address-cells = <1>;
+            port@0 { reg = <0>; dsi_in: endpoint { }; };
+            port@1 { reg = <2>; dsi_out: endpoint { remote-endpoint = <&panel_in>; }; };
+        };
+    };
+    panel {
+        port { panel_in: endpoint { remote-endpoint = <&bridge_in>; }; };
+    };
+    bridge: bridge {
+        port { bridge_in: endpoint { remote-endpoint = <&panel_in>; }; };
+        endpoint { remote-endpoint = <&bridge>; };
+    };
+};
+"#;
+    let mut registry = Registry::empty();
+    register(&mut registry);
+    let (warnings, errors) = super::run_on_source(source, &registry);
+    assert_eq!(
+        warnings,
+        [
+            "5: (graph_child_address) /dsi/ports: graph node should have #size-cells = <0>",
+            "8: (graph_port) /dsi/ports/port@1: graph node unit address error, expected \"2\"",
+            "16: (graph_endpoint) /bridge/endpoint: graph endpoint's parent should be a port",
+            "7: (graph_endpoint) /dsi/ports/port@0/endpoint: graph endpoint has no remote-endpoint",
+            "8: (graph_endpoint) /dsi/ports/port@1/endpoint/remote-endpoint: graph connection to /panel/port/endpoint is not bidirectional",
+        ]
+    );
+    assert!(errors.is_empty());
+}
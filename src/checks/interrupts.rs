@@ -0,0 +1,202 @@
+//! Checks of the interrupt tree:  `interrupt-parent`, `interrupts`, `interrupts-extended`, and
+//! the `interrupt-map` of nexus nodes.
+
+use super::{Check, Context, Registry, Reporter};
+use crate::interrupts::is_interrupt_target;
+
+pub(super) fn register(registry: &mut Registry) {
+    registry.register(Box::new(InterruptProvider));
+    registry.register(Box::new(InterruptsProperty));
+    registry.register(Box::new(InterruptMap));
+}
+
+/// Interrupt controllers and nexus nodes must say how many cells their specifiers have, and
+/// nodes which do so must be one or the other.
+struct InterruptProvider;
+
+impl Check for InterruptProvider {
+    fn name(&self) -> &'static str {
+        "interrupt_provider"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            let has_cells = node.get_property("#interrupt-cells").is_some();
+            if is_interrupt_target(node) && !has_cells {
+                report.node(&path, "interrupt provider has no #interrupt-cells property");
+            }
+            if has_cells && !is_interrupt_target(node) {
+                let message = "node has #interrupt-cells, but no interrupt-controller or \
+                               interrupt-map property";
+                report.node(&path, message);
+            }
+        }
+    }
+}
+
+/// Each interrupt specifier must suit the controller it is delivered to.
+struct InterruptsProperty;
+
+impl Check for InterruptsProperty {
+    fn name(&self) -> &'static str {
+        "interrupts_property"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            // Malformed lists are reported by interrupts_extended_property.
+            if let Some(list) = node.get_cells("interrupts-extended")
+                && let Ok(specifiers) = ctx.phandle_args(&list, "#interrupt-cells", None)
+            {
+                for specifier in specifiers {
+                    if !is_interrupt_target(specifier.node) {
+                        let message = format!(
+                            "{} is not an interrupt controller or nexus",
+                            specifier.provider
+                        );
+                        report.property(&path, "interrupts-extended", message);
+                    }
+                }
+            }
+            let Some(interrupts) = node.get_property("interrupts") else {
+                continue;
+            };
+            if node.get_property("interrupts-extended").is_some() {
+                // interrupts-extended takes precedence, and interrupts is only for old clients.
+                continue;
+            }
+            let (parent_path, parent) = match ctx.interrupt_parent(&path) {
+                Ok(parent) => parent,
+                Err(message) => {
+                    report.property(&path, "interrupts", message);
+                    continue;
+                }
+            };
+            if !is_interrupt_target(parent) {
+                let message = format!(
+                    "interrupt parent {parent_path} is not an interrupt controller or nexus"
+                );
+                report.property(&path, "interrupts", message);
+                continue;
+            }
+            let Some(cells) = parent.get_u32("#interrupt-cells") else {
+                let message = format!("interrupt parent {parent_path} has no #interrupt-cells");
+                report.property(&path, "interrupts", message);
+                continue;
+            };
+            let n = interrupts.len();
+            if n == 0 || cells == 0 || !n.is_multiple_of(4 * cells as usize) {
+                let message = format!(
+                    "size ({n} bytes) is not a multiple of #interrupt-cells ({cells}) of \
+                     {parent_path}"
+                );
+                report.property(&path, "interrupts", message);
+            }
+        }
+    }
+}
+
+/// Each `interrupt-map` entry consists of a child unit address and specifier (sized by the
+/// nexus node's `#address-cells` and `#interrupt-cells`), a parent phandle, and a parent unit
+/// address and specifier (sized by the parent's).
+struct InterruptMap;
+
+impl Check for InterruptMap {
+    fn name(&self) -> &'static str {
+        "interrupt_map"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            if node.get_property("interrupt-map").is_none() {
+                continue;
+            }
+            if node.get_cells("interrupt-map").is_none() {
+                report.property(&path, "interrupt-map", "property is not a list of cells");
+                continue;
+            }
+            let Some(child_interrupt_cells) = node.get_u32("#interrupt-cells") else {
+                // Reported by interrupt_provider.
+                continue;
+            };
+            let Some(child_address_cells) = node.get_u32("#address-cells") else {
+                report.node(&path, "interrupt nexus has no #address-cells property");
+                continue;
+            };
+            let mask_len = (child_address_cells as usize)
+                .checked_add(child_interrupt_cells as usize)
+                .and_then(|cells| cells.checked_mul(4));
+            let Some(mask_len) = mask_len else {
+                let message = format!(
+                    "invalid cell count (#address-cells == {child_address_cells}, \
+                     #interrupt-cells == {child_interrupt_cells})"
+                );
+                report.node(&path, message);
+                continue;
+            };
+            if let Some(mask) = node.get_property("interrupt-map-mask")
+                && mask.len() != mask_len
+            {
+                let message = format!(
+                    "size ({} bytes) should be {mask_len} (#address-cells + #interrupt-cells)",
+                    mask.len()
+                );
+                report.property(&path, "interrupt-map-mask", message);
+            }
+            if let Err(message) = ctx.interrupt_resolver().map_entries(&path) {
+                report.property(&path, "interrupt-map", message);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_interrupts() {
+    let source = r#"
+/dts-v1/;
+/ {
+    interrupt-parent = <&gic>;
+    gic: gic { interrupt-controller; #interrupt-cells = <3>; };
+    intc: intc { interrupt-controller; };
+    plain: plain { #interrupt-cells = <1>; };
+    good { interrupts = <0 1 4>, <0 2 4>; };
+    short { interrupts = <0 1>; };
+    orphan { interrupt-parent = <&intc>; interrupts = <1>; };
+    ext { interrupts-extended = <&gic 0 1 4>, <&plain 1>; };
+    pci {
+        #address-cells = <3>;
+        #interrupt-cells = <1>;
+        interrupt-map-mask = <0 0 0 7>;
+        interrupt-map = <0 0 0 1 &gic 0 5 4>, <0 0 0 2 &gic 0 6>;
+    };
+    gpio {
+        interrupt-controller;
+        #interrupt-cells = <2>;
+        key { interrupts = <3 1>; };
+    };
+    huge {
+        #address-cells = <0xffffffff>;
+        #interrupt-cells = <1>;
+        interrupt-map-mask = <0 7>;
+        interrupt-map = <0 1 &gic 0 7 4>;
+    };
+};
+"#;
+    let mut registry = Registry::empty();
+    register(&mut registry);
+    let (warnings, errors) = super::run_on_source(source, &registry);
+    assert_eq!(
+        warnings,
+        [
+            "6: (interrupt_provider) /intc: interrupt provider has no #interrupt-cells property",
+            "7: (interrupt_provider) /plain: node has #interrupt-cells, but no interrupt-controller or interrupt-map property",
+            "9: (interrupts_property) /short/interrupts: size (8 bytes) is not a multiple of #interrupt-cells (3) of /gic",
+            "10: (interrupts_property) /orphan/interrupts: interrupt parent /intc has no #interrupt-cells",
+            "11: (interrupts_property) /ext/interrupts-extended: /plain is not an interrupt controller or nexus",
+            "16: (interrupt_map) /pci/interrupt-map: entry 1 is truncated",
+            "26: (interrupt_map) /huge/interrupt-map-mask: size (8 bytes) should be 17179869184 (#address-cells + #interrupt-cells)",
+            "27: (interrupt_map) /huge/interrupt-map: entry 0 is truncated",
+        ]
+    );
+    assert!(errors.is_empty());
+}
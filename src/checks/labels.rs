@@ -0,0 +1,505 @@
+//! Checks of node labels across the whole source, including definitions which did not survive
+//! merging.
+//!
+//! `merge::merge()` resolves labels as it goes, and a label vanishes with the node it is
+//! attached to, after which it may be defined again.  References in property values are resolved
+//! only after merging, so they may name a different node than the label did where the reference
+//! was written.
+
+use super::{Check, Context, Level, Registry, Reporter};
+use crate::eval::PropertyReferencesExt;
+use crate::merge::{NodeChange, PropChange};
+use crate::parse::TypedRuleExt;
+use crate::parse::rules::*;
+use crate::path::NodePath;
+use pest::Span;
+use std::collections::{HashMap, HashSet};
+
+pub(super) fn register(registry: &mut Registry) {
+    registry.register(Box::new(UnusedLabel));
+    registry.register(Box::new(DuplicateLabel));
+    registry.register(Box::new(LabelRedefinition));
+    registry.register(Box::new(DeletedNodeReference));
+}
+
+/// The position of a span in merge order:  the index of the enclosing top-level definition,
+/// then the offset within it.  (Included files are separate buffers, so offsets alone are not
+/// comparable.)
+type Order = (usize, usize);
+
+/// One definition of a label.
+struct LabelDef<'a> {
+    name: &'a str,
+    span: Span<'a>,
+    path: NodePath,
+    order: Order,
+}
+
+/// Label definitions and references, gathered from the parsed source.
+pub(super) struct Labels<'a> {
+    /// The extent and index of each top-level definition, by input, in order of position.
+    top_def_spans: HashMap<usize, Vec<(usize, usize, usize)>>,
+    /// Each definition in merge order.
+    defs: Vec<LabelDef<'a>>,
+    /// The indices in `defs` of the definitions of each label.
+    defs_by_name: HashMap<&'a str, Vec<usize>>,
+    /// The span of each definition in `defs`.
+    def_spans: HashSet<(usize, usize)>,
+    /// The name of each label referenced anywhere, even in definitions which were later deleted.
+    referenced: HashSet<&'a str>,
+    /// The text of each reference by path, as in `&{/soc/uart@1000}` or `${/chosen/x}`, likewise.
+    path_references: Vec<&'a str>,
+}
+
+/// Identify a span within the loaded sources.
+fn span_key(span: &Span) -> (usize, usize) {
+    (span.get_input().as_ptr() as usize, span.start())
+}
+
+/// The label a node or property path starts with, if any.
+fn path_label(path: &str) -> Option<&str> {
+    let first = path.split('/').next().unwrap();
+    (!first.is_empty() && first != ".").then_some(first)
+}
+
+fn node_reference_label<'a>(noderef: &NodeReference<'a>) -> Option<&'a str> {
+    path_label(noderef.str().trim_matches(['&', '{', '}']))
+}
+
+/// The label a property reference starts with, if any.  `$name` names a property of the
+/// current node.
+fn property_reference_label<'a>(propref: &PropertyReference<'a>) -> Option<&'a str> {
+    let (node, _) = propref
+        .str()
+        .trim_matches(['$', '{', '}'])
+        .rsplit_once('/')?;
+    path_label(node)
+}
+
+/// Collect the node references within a value, in source order.
+fn node_references<'a>(propvalue: &PropValue<'a>, out: &mut Vec<&'a NodeReference<'a>>) {
+    for labeled_value in propvalue.labeled_value {
+        match labeled_value.value {
+            Value::NodeReference(noderef) => out.push(noderef),
+            Value::Cells(cells) => {
+                for label_or_cell in cells.label_or_cell {
+                    if let LabelOrCell::Cell(Cell::NodeReference(noderef)) = label_or_cell {
+                        out.push(noderef);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The labels of the source, gathered once per `Context` and shared by the checks below.
+fn labels<'c, 'a>(ctx: &'c Context<'_, 'a>) -> Option<&'c Labels<'a>> {
+    ctx.source_labels.get_or_init(|| Labels::new(ctx)).as_ref()
+}
+
+impl Context<'_, '_> {
+    /// Whether a reference anywhere in the source names a node called `name` along its path, as
+    /// `&{/soc/uart@1000}` names `soc` and `uart@1000`.
+    pub(super) fn referenced_by_path(&self, name: &str) -> bool {
+        let Some(labels) = labels(self) else {
+            return false;
+        };
+        labels.path_references.iter().any(|r| {
+            let path = r.trim_start_matches(['&', '$']).trim_matches(['{', '}']);
+            path.split('/').any(|segment| segment == name)
+        })
+    }
+}
+
+impl<'a> Labels<'a> {
+    fn new(ctx: &Context<'_, 'a>) -> Option<Self> {
+        let source = ctx.source.as_ref()?;
+        // Find the node defined by each TopNode and ChildNode which was merged.
+        let mut paths = HashMap::new();
+        for (path, changes) in source.node_changes {
+            for change in changes {
+                if let NodeChange::TopNode(_) | NodeChange::ChildNode(_) = change {
+                    paths.insert(span_key(change.span()), path);
+                }
+            }
+        }
+        let mut top_def_spans: HashMap<_, Vec<_>> = HashMap::new();
+        for (i, top_def) in source.dts.top_def.iter().enumerate() {
+            let span = top_def.span();
+            let (input, start) = span_key(span);
+            top_def_spans
+                .entry(input)
+                .or_default()
+                .push((start, span.end(), i));
+        }
+        for spans in top_def_spans.values_mut() {
+            // Stable, so that of a file included twice, the first inclusion comes first.
+            spans.sort_by_key(|&(start, _, _)| start);
+        }
+        let mut labels = Labels {
+            top_def_spans,
+            defs: vec![],
+            defs_by_name: HashMap::new(),
+            def_spans: HashSet::new(),
+            referenced: HashSet::new(),
+            path_references: vec![],
+        };
+        for (i, top_def) in source.dts.top_def.iter().enumerate() {
+            match top_def {
+                TopDef::TopNode(topnode) => {
+                    if let TopNodeName::NodeReference(noderef) = topnode.top_node_name {
+                        labels.add_node_reference(noderef);
+                    }
+                    if let Some(path) = paths.get(&span_key(topnode.span())) {
+                        for label in topnode.label {
+                            labels.add_def(label, path, i);
+                        }
+                    }
+                    labels.visit_body(topnode.node_body, &paths, i);
+                }
+                TopDef::TopDelNode(topdelnode) => {
+                    labels.add_node_reference(topdelnode.node_reference);
+                }
+                TopDef::TopOmitNode(topomitnode) => {
+                    labels.add_node_reference(topomitnode.node_reference);
+                }
+                TopDef::Header(_) | TopDef::Include(_) | TopDef::Memreserve(_) => (),
+            }
+        }
+        Some(labels)
+    }
+
+    fn add_def(&mut self, label: &Label<'a>, path: &NodePath, top_def: usize) {
+        let span = *label.span();
+        // A file included twice defines its labels twice, but in the same place.
+        if !self.def_spans.insert(span_key(&span)) {
+            return;
+        }
+        let name = label.str().strip_suffix(':').unwrap();
+        self.defs_by_name
+            .entry(name)
+            .or_default()
+            .push(self.defs.len());
+        self.defs.push(LabelDef {
+            name,
+            span,
+            path: path.clone(),
+            order: (top_def, span.start()),
+        });
+    }
+
+    fn add_node_reference(&mut self, noderef: &NodeReference<'a>) {
+        self.referenced.extend(node_reference_label(noderef));
+        if noderef.str().starts_with("&{") {
+            self.path_references.push(noderef.str());
+        }
+    }
+
+    fn visit_body(
+        &mut self,
+        body: &NodeBody<'a>,
+        paths: &HashMap<(usize, usize), &NodePath>,
+        top_def: usize,
+    ) {
+        for prop_def in body.node_contents.prop_def {
+            if let PropDef::Prop(Prop {
+                prop_value: Some(propvalue),
+                ..
+            }) = prop_def
+            {
+                let mut noderefs = vec![];
+                node_references(propvalue, &mut noderefs);
+                for noderef in noderefs {
+                    self.add_node_reference(noderef);
+                }
+                let mut proprefs = vec![];
+                propvalue.property_references(&mut proprefs);
+                for propref in proprefs {
+                    self.referenced.extend(property_reference_label(propref));
+                    if propref.str().starts_with("${") {
+                        self.path_references.push(propref.str());
+                    }
+                }
+            }
+        }
+        for child_def in body.node_contents.child_def {
+            if let ChildDef::ChildNode(childnode) = child_def {
+                if let Some(path) = paths.get(&span_key(childnode.span())) {
+                    for prefix in childnode.child_node_prefix {
+                        if let ChildNodePrefix::Label(label) = prefix {
+                            self.add_def(label, path, top_def);
+                        }
+                    }
+                }
+                self.visit_body(childnode.node_body, paths, top_def);
+            }
+        }
+    }
+
+    /// The position of `span` in merge order, if it lies within a top-level definition.
+    fn order(&self, span: &Span) -> Option<Order> {
+        let (input, start) = span_key(span);
+        let spans = self.top_def_spans.get(&input)?;
+        // Top-level definitions in one input overlap only if they are the same definition,
+        // included more than once, so the first which starts at or before `span` is the one.
+        let after = spans.partition_point(|&(s, _, _)| s <= start);
+        let (outer_start, outer_end, _) = *spans[..after].last()?;
+        let first = spans.partition_point(|&(s, _, _)| s < outer_start);
+        let (_, _, i) = spans[first];
+        (span.end() <= outer_end).then_some((i, start))
+    }
+
+    /// The definitions of `name`, in merge order.
+    fn defs_of(&self, name: &str) -> impl DoubleEndedIterator<Item = &LabelDef<'a>> {
+        let indices = self.defs_by_name.get(name).map_or(&[][..], Vec::as_slice);
+        indices.iter().map(|&i| &self.defs[i])
+    }
+
+    /// The definition of the same label before `defs[i]`, if any.
+    fn previous_def(&self, i: usize) -> Option<&LabelDef<'a>> {
+        let indices = &self.defs_by_name[self.defs[i].name];
+        let k = indices.binary_search(&i).unwrap();
+        Some(&self.defs[*indices[..k].last()?])
+    }
+
+    /// The definition of `name` in effect at `order`.
+    fn def_at(&self, name: &str, order: Order) -> Option<&LabelDef<'a>> {
+        self.defs_of(name).rfind(|d| d.order < order)
+    }
+
+    /// The definition of `name` in the merged tree.
+    fn last_def(&self, name: &str) -> Option<&LabelDef<'a>> {
+        self.defs_of(name).next_back()
+    }
+
+    /// Whether the node at `path` is deleted after `order`.
+    fn deleted_after(&self, ctx: &Context, path: &NodePath, order: Order) -> bool {
+        let source = ctx.source.as_ref().unwrap();
+        let Some(changes) = source.node_changes.get(path) else {
+            return false;
+        };
+        changes.iter().any(|change| {
+            matches!(change, NodeChange::TopDelNode(_) | NodeChange::DelNode(_))
+                && self.order(change.span()).is_some_and(|o| o > order)
+        })
+    }
+}
+
+/// Call `f` with the path and name of each property in the evaluated tree, and the value it was
+/// given in source.
+fn for_each_value<'a>(ctx: &Context<'_, 'a>, mut f: impl FnMut(&NodePath, &str, &PropValue<'a>)) {
+    let Some(source) = &ctx.source else {
+        return;
+    };
+    for (path, node) in ctx.nodes() {
+        for (name, _) in node.properties() {
+            let changes = source.prop_changes.get(&path.join(name));
+            if let Some(PropChange::Prop(prop)) = changes.and_then(|c| c.last())
+                && let Some(propvalue) = prop.prop_value
+            {
+                f(&path, name, propvalue);
+            }
+        }
+    }
+}
+
+/// Labels are often defined for use by overlays, or by boards including a common file, so
+/// this is off by default.
+struct UnusedLabel;
+
+impl Check for UnusedLabel {
+    fn name(&self) -> &'static str {
+        "unused_label"
+    }
+
+    fn default_level(&self) -> Level {
+        Level::Off
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let Some(labels) = labels(ctx) else {
+            return;
+        };
+        let mut reported = HashSet::new();
+        for def in &labels.defs {
+            if !labels.referenced.contains(def.name) && reported.insert(def.name) {
+                let message = format!("label \"{}\" is never referenced", def.name);
+                report.at(Some(def.span), &def.path, message);
+            }
+        }
+    }
+}
+
+/// A label may be defined again after the node it labeled is deleted, or repeated on the same
+/// node, but either is likely to confuse.
+struct DuplicateLabel;
+
+impl Check for DuplicateLabel {
+    fn name(&self) -> &'static str {
+        "duplicate_label"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let Some(labels) = labels(ctx) else {
+            return;
+        };
+        for (i, def) in labels.defs.iter().enumerate() {
+            if let Some(previous) = labels.previous_def(i) {
+                let message = format!(
+                    "label \"{}\" was previously defined on {}",
+                    def.name, previous.path
+                );
+                report.at(Some(def.span), &def.path, message);
+            }
+        }
+    }
+}
+
+/// A node reference which resolves only because its label was defined again after the node it
+/// named was deleted.
+struct LabelRedefinition;
+
+impl Check for LabelRedefinition {
+    fn name(&self) -> &'static str {
+        "label_redefinition"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let Some(labels) = labels(ctx) else {
+            return;
+        };
+        for_each_value(ctx, |path, name, propvalue| {
+            let mut noderefs = vec![];
+            node_references(propvalue, &mut noderefs);
+            for noderef in noderefs {
+                let Some(label) = node_reference_label(noderef) else {
+                    continue;
+                };
+                let Some(order) = labels.order(noderef.span()) else {
+                    continue;
+                };
+                let (Some(then), Some(now)) = (labels.def_at(label, order), labels.last_def(label))
+                else {
+                    continue;
+                };
+                if then.span != now.span && labels.deleted_after(ctx, &then.path, then.order) {
+                    let message = format!(
+                        "{} refers to {}, because \"{label}\" was defined again after {}, which it \
+                         labels here, was deleted",
+                        noderef.str(),
+                        now.path,
+                        then.path
+                    );
+                    report.at(Some(*noderef.span()), &path.join(name), message);
+                }
+            }
+        });
+    }
+}
+
+/// A property reference into a node which is deleted after the reference, so that it resolves
+/// to a property defined later, if at all.
+struct DeletedNodeReference;
+
+impl Check for DeletedNodeReference {
+    fn name(&self) -> &'static str {
+        "deleted_node_reference"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let Some(labels) = labels(ctx) else {
+            return;
+        };
+        for_each_value(ctx, |path, name, propvalue| {
+            let mut proprefs = vec![];
+            propvalue.property_references(&mut proprefs);
+            for propref in proprefs {
+                let Some((node, _)) = propref.str().trim_matches(['$', '{', '}']).rsplit_once('/')
+                else {
+                    continue;
+                };
+                let Some(order) = labels.order(propref.span()) else {
+                    continue;
+                };
+                // The node named where the reference is written.
+                let then = match property_reference_label(propref) {
+                    Some(label) => match labels.def_at(label, order) {
+                        Some(def) => {
+                            let mut then = def.path.clone();
+                            for segment in node.split('/').skip(1).filter(|s| !s.is_empty()) {
+                                then.push(segment);
+                            }
+                            then
+                        }
+                        None => continue,
+                    },
+                    None if node.starts_with('/') => match ctx.resolve_path(node) {
+                        Some(then) => then,
+                        None => continue,
+                    },
+                    None => continue,
+                };
+                if labels.deleted_after(ctx, &then, order) {
+                    let now = ctx.resolve_path(node);
+                    let message = match now {
+                        Some(now) if now != then => format!(
+                            "{} refers into {then}, which is deleted later, so it resolves to \
+                             {now} instead",
+                            propref.str()
+                        ),
+                        _ => format!(
+                            "{} refers into {then}, which is deleted and defined again later",
+                            propref.str()
+                        ),
+                    };
+                    report.at(Some(*propref.span()), &path.join(name), message);
+                }
+            }
+        });
+    }
+}
+
+#[test]
+fn test_labels() {
+    let source = r#"
+/dts-v1/;
+/ {
+    unused: a { };
+    old: b { x = <1>; };
+    c {
+        phandle-ref = <&old>;
+        prop-ref = <${old/x}>;
+        path-ref = <${/d/y}>;
+        forward = <&later>;
+    };
+    d { y = <2>; };
+    later: e { };
+};
+/delete-node/ &old;
+/ {
+    old: f { x = <3>; };
+    d { };
+};
+&{/d} { again: g { }; };
+/delete-node/ &{/d};
+/ { d { y = <4>; }; };
+&later { };
+"#;
+    let mut registry = Registry::empty();
+    register(&mut registry);
+    registry.set_level("unused_label", Level::Warning).unwrap();
+    let (warnings, errors) = super::run_on_source(source, &registry);
+    assert_eq!(
+        warnings,
+        [
+            "4: (unused_label) /a: label \"unused\" is never referenced",
+            "20: (unused_label) /d/g: label \"again\" is never referenced",
+            "17: (duplicate_label) /f: label \"old\" was previously defined on /b",
+            "7: (label_redefinition) /c/phandle-ref: &old refers to /f, because \"old\" was defined again after /b, which it labels here, was deleted",
+            "8: (deleted_node_reference) /c/prop-ref: ${old/x} refers into /b, which is deleted later, so it resolves to /f instead",
+            "9: (deleted_node_reference) /c/path-ref: ${/d/y} refers into /d, which is deleted and defined again later",
+        ]
+    );
+    assert!(errors.is_empty());
+}
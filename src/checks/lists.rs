@@ -0,0 +1,170 @@
+//! Checks of `*-names` properties against the lists whose entries they name, as in
+//! `clocks = <&osc>, <&pll 2>; clock-names = "ref", "core";`.
+
+use super::{Check, Context, PHANDLE_ARGS, Registry, Reporter};
+use crate::BinaryNode;
+use crate::path::NodePath;
+
+pub(super) fn register(registry: &mut Registry) {
+    registry.register(Box::new(NamesCount));
+}
+
+/// Lists and the properties naming their entries.  `interrupts` stands for `interrupts-extended`
+/// too, and `pinctrl-0` for the `pinctrl-N` properties.
+const NAMED_LISTS: &[(&str, &str)] = &[
+    ("clocks", "clock-names"),
+    ("dmas", "dma-names"),
+    ("hwlocks", "hwlock-names"),
+    ("interrupts", "interrupt-names"),
+    ("io-channels", "io-channel-names"),
+    ("mboxes", "mbox-names"),
+    ("phys", "phy-names"),
+    ("pinctrl-0", "pinctrl-names"),
+    ("power-domains", "power-domain-names"),
+    ("pwms", "pwm-names"),
+    ("reg", "reg-names"),
+    ("resets", "reset-names"),
+];
+
+/// The number of entries in the list `name` of the node at `path`, or `None` if it cannot be
+/// counted.  Malformed lists are reported by their own checks.
+fn count(ctx: &Context, path: &NodePath, node: &BinaryNode, name: &str) -> Option<usize> {
+    let cells_per_entry = match name {
+        "reg" => {
+            let parent = ctx.parent(path)?;
+            (parent.address_cells() as usize).checked_add(parent.size_cells() as usize)?
+        }
+        "interrupts" => {
+            let (_, parent) = ctx.interrupt_parent(path).ok()?;
+            parent.get_u32("#interrupt-cells")? as usize
+        }
+        "pinctrl-0" => {
+            let states = (0..).take_while(|n| node.get_property(&format!("pinctrl-{n}")).is_some());
+            return Some(states.count());
+        }
+        _ => {
+            let list = node.get_cells(name)?;
+            let phandle_args = PHANDLE_ARGS.iter().find(|p| p.matches(name))?;
+            let entries = ctx
+                .phandle_entries(&list, phandle_args.cells, phandle_args.default_cells)
+                .ok()?;
+            return Some(entries.len());
+        }
+    };
+    let len = node.get_property(name)?.len();
+    let entry_len = cells_per_entry.checked_mul(4)?;
+    (entry_len != 0 && len.is_multiple_of(entry_len)).then(|| len / entry_len)
+}
+
+/// A `*-names` property has one name for each entry of its list.
+struct NamesCount;
+
+impl Check for NamesCount {
+    fn name(&self) -> &'static str {
+        "names_count"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            for &(list, names) in NAMED_LISTS {
+                if node.get_property(names).is_none() {
+                    continue;
+                }
+                let Some(names_len) = node.get_strings(names).map(|n| n.len()) else {
+                    report.property(&path, names, "property is not a string list");
+                    continue;
+                };
+                let list = match list {
+                    "interrupts" if node.get_property("interrupts-extended").is_some() => {
+                        "interrupts-extended"
+                    }
+                    _ => list,
+                };
+                let names_has = plural(names_len, "name", "names");
+                if node.get_property(list).is_none() {
+                    let message = format!("has {names_has}, but there is no {list} property");
+                    report.property(&path, names, message);
+                    continue;
+                }
+                let Some(list_len) = count(ctx, &path, node, list) else {
+                    continue;
+                };
+                if list_len == names_len {
+                    continue;
+                }
+                let (names_message, list_message) = match list {
+                    "pinctrl-0" => {
+                        let states = plural(list_len, "pinctrl-N property", "pinctrl-N properties");
+                        (
+                            format!("has {names_has}, but there are {states}"),
+                            format!("has {states}, but {names} has {names_has}"),
+                        )
+                    }
+                    _ => {
+                        let entries = plural(list_len, "entry", "entries");
+                        (
+                            format!("has {names_has}, but {list} has {entries}"),
+                            format!("has {entries}, but {names} has {names_has}"),
+                        )
+                    }
+                };
+                report.property(&path, names, names_message);
+                report.property(&path, list, list_message);
+            }
+        }
+    }
+}
+
+fn plural(n: usize, one: &str, many: &str) -> String {
+    match n {
+        1 => format!("1 {one}"),
+        _ => format!("{n} {many}"),
+    }
+}
+
+#[test]
+fn test_lists() {
+    let source = r#"
+/dts-v1/;
+/ {
+    #address-cells = <1>;
+    #size-cells = <1>;
+    clk: clock { #clock-cells = <1>; };
+    intc: intc { interrupt-controller; #interrupt-cells = <2>; };
+    dev@1000 {
+        reg = <0x1000 0x100>, <0x2000 0x100>;
+        reg-names = "regs";
+        clocks = <&clk 0>, <0>, <&clk 1>;
+        clock-names = "a", "b", "c";
+        interrupt-parent = <&intc>;
+        interrupts = <1 2>, <3 4>;
+        interrupt-names = "tx", "rx", "err";
+        pinctrl-0 = <>;
+        pinctrl-1 = <>;
+        pinctrl-names = "default";
+        reset-names = "core";
+    };
+    huge {
+        #address-cells = <0xffffffff>;
+        #size-cells = <1>;
+        dev@0 { reg = <0 1>; reg-names = "a"; };
+    };
+};
+"#;
+    let mut registry = Registry::empty();
+    register(&mut registry);
+    let (warnings, errors) = super::run_on_source(source, &registry);
+    assert_eq!(
+        warnings,
+        [
+            "15: (names_count) /dev@1000/interrupt-names: has 3 names, but interrupts has 2 entries",
+            "14: (names_count) /dev@1000/interrupts: has 2 entries, but interrupt-names has 3 names",
+            "18: (names_count) /dev@1000/pinctrl-names: has 1 name, but there are 2 pinctrl-N properties",
+            "16: (names_count) /dev@1000/pinctrl-0: has 2 pinctrl-N properties, but pinctrl-names has 1 name",
+            "10: (names_count) /dev@1000/reg-names: has 1 name, but reg has 2 entries",
+            "9: (names_count) /dev@1000/reg: has 2 entries, but reg-names has 1 name",
+            "19: (names_count) /dev@1000/reset-names: has 1 name, but there is no resets property",
+        ]
+    );
+    assert!(errors.is_empty());
+}
@@ -0,0 +1,244 @@
+//! Checks of node and property names against the devicetree specification.
+//!
+//! The grammar accepts the characters dtc does, which are a superset of those permitted by the
+//! specification.  Node names have the form `node-name@unit-address`.
+
+use super::{Check, Context, Level, Registry, Reporter};
+
+pub(super) fn register(registry: &mut Registry) {
+    registry.register(Box::new(NodeNameChars));
+    registry.register(Box::new(NodeNameCharsStrict));
+    registry.register(Box::new(NodeNameFormat));
+    registry.register(Box::new(PropertyNameChars));
+    registry.register(Box::new(NameLength));
+    registry.register(Box::new(UnitAddressFormat));
+}
+
+/// The longest node base name or property name permitted by the specification.
+const MAX_NAME_LEN: usize = 31;
+
+fn is_node_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ",._+-".contains(c)
+}
+
+fn is_property_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ",._+?#-".contains(c)
+}
+
+/// Call `f` with the path and name of each node other than the root.
+fn for_each_named_node(ctx: &Context, mut f: impl FnMut(&crate::path::NodePath, &str)) {
+    for (path, _) in ctx.nodes() {
+        if !path.is_root() {
+            f(&path, path.leaf());
+        }
+    }
+}
+
+struct NodeNameChars;
+
+impl Check for NodeNameChars {
+    fn name(&self) -> &'static str {
+        "node_name_chars"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for_each_named_node(ctx, |path, name| {
+            if let Some(c) = name.chars().find(|&c| c != '@' && !is_node_char(c)) {
+                let message = format!("character '{c}' is not permitted in node names");
+                report.node(path, message);
+            }
+        });
+    }
+}
+
+/// Uppercase letters and underscores are permitted, but discouraged.  Uppercase letters are
+/// sought only in the base name; `unit_address_format` reports an uppercase unit address.
+struct NodeNameCharsStrict;
+
+impl Check for NodeNameCharsStrict {
+    fn name(&self) -> &'static str {
+        "node_name_chars_strict"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for_each_named_node(ctx, |path, name| {
+            let base = name.split('@').next().unwrap();
+            if base.contains(|c: char| c.is_ascii_uppercase()) {
+                report.node(path, "node name should not contain uppercase letters");
+            }
+            if name.contains('_') {
+                report.node(path, "node name should not contain underscores");
+            }
+        });
+    }
+}
+
+struct NodeNameFormat;
+
+impl Check for NodeNameFormat {
+    fn name(&self) -> &'static str {
+        "node_name_format"
+    }
+
+    fn default_level(&self) -> Level {
+        Level::Error
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for_each_named_node(ctx, |path, name| {
+            if name.matches('@').count() > 1 {
+                report.node(path, "node name has more than one '@'");
+            } else if name.starts_with('@') {
+                report.node(path, "node name has a unit address but no base name");
+            }
+        });
+    }
+}
+
+struct PropertyNameChars;
+
+impl Check for PropertyNameChars {
+    fn name(&self) -> &'static str {
+        "property_name_chars"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            for (name, _) in node.properties() {
+                if let Some(c) = name.chars().find(|&c| !is_property_char(c)) {
+                    let message = format!("character '{c}' is not permitted in property names");
+                    report.property_name(&path, name, message);
+                }
+            }
+        }
+    }
+}
+
+struct NameLength;
+
+impl Check for NameLength {
+    fn name(&self) -> &'static str {
+        "name_length"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            if !path.is_root() {
+                let (base, _) = path.leaf().split_once('@').unwrap_or((path.leaf(), ""));
+                if base.len() > MAX_NAME_LEN {
+                    let n = base.len();
+                    let message = format!(
+                        "node name has {n} characters; at most {MAX_NAME_LEN} are permitted"
+                    );
+                    report.node(&path, message);
+                }
+            }
+            for (name, _) in node.properties() {
+                if name.len() > MAX_NAME_LEN {
+                    let n = name.len();
+                    let message = format!(
+                        "property name has {n} characters; at most {MAX_NAME_LEN} are permitted"
+                    );
+                    report.property_name(&path, name, message);
+                }
+            }
+        }
+    }
+}
+
+/// Unit addresses are written in lowercase hex, without "0x" or leading 0s.  Each finding
+/// suggests the unit address with every comma-separated part rewritten so.
+struct UnitAddressFormat;
+
+/// One part of a unit address in lowercase hex without "0x" or leading 0s, or else unchanged.
+fn normalize_address_part(part: &str) -> String {
+    let digits = part.trim_start_matches("0x").trim_start_matches("0X");
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return part.into();
+    }
+    let digits = digits.trim_start_matches('0');
+    match digits.is_empty() {
+        true => "0".into(),
+        false => digits.to_ascii_lowercase(),
+    }
+}
+
+impl Check for UnitAddressFormat {
+    fn name(&self) -> &'static str {
+        "unit_address_format"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for_each_named_node(ctx, |path, name| {
+            let Some((base, address)) = name.split_once('@') else {
+                return;
+            };
+            if address.is_empty() {
+                report.node(path, "unit address is empty");
+                return;
+            }
+            let parts: Vec<_> = address.split(',').map(normalize_address_part).collect();
+            let fixed = format!("{base}@{}", parts.join(","));
+            for part in address.split(',') {
+                let is_hex = part.chars().all(|c| c.is_ascii_hexdigit());
+                let message = if part.starts_with("0x") || part.starts_with("0X") {
+                    "unit address should not have leading \"0x\""
+                } else if part.len() > 1 && part.starts_with('0') && is_hex {
+                    "unit address should not have leading 0s"
+                } else if is_hex && part.chars().any(|c| c.is_ascii_uppercase()) {
+                    "unit address should be lowercase"
+                } else {
+                    continue;
+                };
+                let fix = (fixed != name)
+                    .then(|| ctx.rename_node(path, &fixed))
+                    .flatten();
+                report.node_with_fix(path, message, fix);
+            }
+        });
+    }
+}
+
+#[test]
+fn test_names() {
+    let source = r#"
+/dts-v1/;
+/ {
+    Serial_0 { };
+    a@1@2 { };
+    @3 { };
+    b#c { };
+    d@0x10 { };
+    e@0010 { };
+    f@ { };
+    thirty-two-characters-long-name0 { };
+    g { x@y = <1>; };
+    h@0 { };
+    i@1A { };
+};
+"#;
+    let mut registry = Registry::empty();
+    register(&mut registry);
+    let (warnings, errors) = super::run_on_source(source, &registry);
+    assert_eq!(
+        warnings,
+        [
+            "7: (node_name_chars) /b#c: character '#' is not permitted in node names",
+            "4: (node_name_chars_strict) /Serial_0: node name should not contain uppercase letters",
+            "4: (node_name_chars_strict) /Serial_0: node name should not contain underscores",
+            "12: (property_name_chars) /g/x@y: character '@' is not permitted in property names",
+            "11: (name_length) /thirty-two-characters-long-name0: node name has 32 characters; at most 31 are permitted",
+            "8: (unit_address_format) /d@0x10: unit address should not have leading \"0x\"",
+            "9: (unit_address_format) /e@0010: unit address should not have leading 0s",
+            "10: (unit_address_format) /f@: unit address is empty",
+            "14: (unit_address_format) /i@1A: unit address should be lowercase",
+        ]
+    );
+    assert_eq!(
+        errors,
+        [
+            "5: (node_name_format) /a@1@2: node name has more than one '@'",
+            "6: (node_name_format) /@3: node name has a unit address but no base name",
+        ]
+    );
+}
@@ -0,0 +1,170 @@
+//! Checks of properties which list phandles of providers, each followed by arguments whose
+//! number is given by the provider, as in `clocks = <&osc 0>, <&pll 2>;`.
+
+use super::{Check, Context, Registry, Reporter};
+
+/// A check of the phandle-and-arguments properties matching `properties`.  Downstream crates
+/// may register further instances for their own bindings.
+#[derive(Clone, Copy)]
+pub struct PhandleArgs {
+    /// The name of the check, as in `-W no-clocks_property`.
+    pub name: &'static str,
+    /// Names of the checked properties.  A leading '*' matches any prefix.
+    pub properties: &'static [&'static str],
+    /// The provider's property giving the number of argument cells, e.g. `#clock-cells`.
+    pub cells: &'static str,
+    /// The number of argument cells if the provider lacks `cells`, or `None` if the property is
+    /// required.
+    pub default_cells: Option<u32>,
+}
+
+/// The built-in checks, after those of dtc.
+pub const PHANDLE_ARGS: &[PhandleArgs] = &[
+    required("clocks_property", &["clocks"], "#clock-cells"),
+    required(
+        "cooling_device_property",
+        &["cooling-device"],
+        "#cooling-cells",
+    ),
+    required("dmas_property", &["dmas"], "#dma-cells"),
+    required("hwlocks_property", &["hwlocks"], "#hwlock-cells"),
+    required(
+        "interconnects_property",
+        &["interconnects"],
+        "#interconnect-cells",
+    ),
+    required(
+        "interrupts_extended_property",
+        &["interrupts-extended"],
+        "#interrupt-cells",
+    ),
+    required(
+        "io_channels_property",
+        &["io-channels"],
+        "#io-channel-cells",
+    ),
+    required("iommus_property", &["iommus"], "#iommu-cells"),
+    required("mboxes_property", &["mboxes"], "#mbox-cells"),
+    PhandleArgs {
+        name: "msi_parent_property",
+        properties: &["msi-parent"],
+        cells: "#msi-cells",
+        default_cells: Some(0),
+    },
+    required(
+        "mux_controls_property",
+        &["mux-controls"],
+        "#mux-control-cells",
+    ),
+    required("phys_property", &["phys"], "#phy-cells"),
+    required(
+        "power_domains_property",
+        &["power-domains"],
+        "#power-domain-cells",
+    ),
+    required("pwms_property", &["pwms"], "#pwm-cells"),
+    required("resets_property", &["resets"], "#reset-cells"),
+    required("sound_dai_property", &["sound-dai"], "#sound-dai-cells"),
+    required(
+        "thermal_sensors_property",
+        &["thermal-sensors"],
+        "#thermal-sensor-cells",
+    ),
+    required(
+        "gpios_property",
+        &["gpios", "*-gpios", "*-gpio"],
+        "#gpio-cells",
+    ),
+];
+
+const fn required(
+    name: &'static str,
+    properties: &'static [&'static str],
+    cells: &'static str,
+) -> PhandleArgs {
+    PhandleArgs {
+        name,
+        properties,
+        cells,
+        default_cells: None,
+    }
+}
+
+/// Properties which match a pattern above, but are not lists of phandles.
+const EXCEPTIONS: &[&str] = &["nr-gpios"];
+
+pub(super) fn register(registry: &mut Registry) {
+    for check in PHANDLE_ARGS {
+        registry.register(Box::new(*check));
+    }
+}
+
+impl PhandleArgs {
+    /// Whether the property `name` is a list of phandles and arguments of this kind.
+    pub fn matches(&self, name: &str) -> bool {
+        !EXCEPTIONS.contains(&name)
+            && self.properties.iter().any(|p| match p.strip_prefix('*') {
+                Some(suffix) => name.len() > suffix.len() && name.ends_with(suffix),
+                None => name == *p,
+            })
+    }
+}
+
+impl Check for PhandleArgs {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            for (name, _) in node.properties() {
+                if !self.matches(name) {
+                    continue;
+                }
+                let Some(list) = node.get_cells(name) else {
+                    report.property(&path, name, "property is not a list of cells");
+                    continue;
+                };
+                if let Err(message) = ctx.phandle_args(&list, self.cells, self.default_cells) {
+                    report.property(&path, name, message);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_phandle_args() {
+    let source = r#"
+/dts-v1/;
+/ {
+    osc: osc { #clock-cells = <0>; };
+    pll: pll { #clock-cells = <1>; };
+    gpio: gpio { #gpio-cells = <2>; };
+    plain: plain { };
+    good {
+        clocks = <&osc>, <&pll 3>;
+        reset-gpios = <&gpio 1 0>, <0>, <&gpio 2 0>;
+        nr-gpios = <4>;
+        msi-parent = <&plain>;
+    };
+    short { clocks = <&osc>, <&pll>; };
+    provider { clocks = <&plain 1>; };
+    dangling { led-gpios = <0x1234 0 0>; };
+    bytes { clocks = [00]; };
+};
+"#;
+    let mut registry = Registry::empty();
+    register(&mut registry);
+    let (warnings, errors) = super::run_on_source(source, &registry);
+    assert_eq!(
+        warnings,
+        [
+            "14: (clocks_property) /short/clocks: entry 1: /pll has #clock-cells = <1>, but 0 cells remain",
+            "15: (clocks_property) /provider/clocks: entry 0: /plain has no #clock-cells property",
+            "17: (clocks_property) /bytes/clocks: property is not a list of cells",
+            "16: (gpios_property) /dangling/led-gpios: entry 0: no node has phandle 0x1234",
+        ]
+    );
+    assert!(errors.is_empty());
+}
@@ -0,0 +1,245 @@
+//! Checks of `reg` properties against unit addresses and the parent's cell sizes.
+
+use super::{Check, Context, Registry, Reporter};
+use crate::address::{Region, memory_map};
+use crate::path::NodePath;
+use std::collections::HashSet;
+
+pub(super) fn register(registry: &mut Registry) {
+    registry.register(Box::new(UnitAddressVsReg));
+    registry.register(Box::new(RegFormat));
+    registry.register(Box::new(AvoidDefaultAddrSize));
+    registry.register(Box::new(RegOverlap));
+}
+
+/// Combine big-endian cells into one number.  Callers must supply at most two cells.
+fn cells_to_u64(cells: &[u32]) -> u64 {
+    cells.iter().fold(0, |n, &c| (n << 32) | c as u64)
+}
+
+/// A node has a unit address if and only if it has `reg` or `ranges`, and the unit address
+/// matches the first address in `reg`.
+struct UnitAddressVsReg;
+
+impl Check for UnitAddressVsReg {
+    fn name(&self) -> &'static str {
+        "unit_address_vs_reg"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            let Some(parent) = ctx.parent(&path) else {
+                continue;
+            };
+            // Ports and endpoints are checked by graph_port and graph_endpoint.
+            if ["port", "endpoint"].contains(&crate::graph::base_name(&path)) {
+                continue;
+            }
+            let has_reg = node.get_property("reg").is_some();
+            let has_ranges = node.get_property("ranges").is_some();
+            let Some((_, unit_address)) = path.leaf().split_once('@') else {
+                if has_reg || has_ranges {
+                    report.node(&path, "node has a reg or ranges property, but no unit name");
+                }
+                continue;
+            };
+            if !has_reg && !has_ranges {
+                report.node(&path, "node has a unit name, but no reg or ranges property");
+                continue;
+            }
+            // Only simple numeric addresses can be compared.  Buses with structured addresses,
+            // such as PCI, have their own checks.  Malformed reg is reported by reg_format.
+            let address_cells = parent.address_cells() as usize;
+            let entry_cells = address_cells + parent.size_cells() as usize;
+            let Some(reg) = node.get_cells("reg") else {
+                continue;
+            };
+            if !(1..=2).contains(&address_cells)
+                || reg.is_empty()
+                || !reg.len().is_multiple_of(entry_cells)
+                || unit_address.contains(',')
+            {
+                continue;
+            }
+            let address = cells_to_u64(&reg[..address_cells]);
+            // Formatting problems are reported by unit_address_format.
+            let digits = unit_address.trim_start_matches("0x");
+            if u64::from_str_radix(digits, 16) != Ok(address) {
+                let message =
+                    format!("unit address does not match reg address (expected \"{address:x}\")");
+                let base = crate::graph::base_name(&path);
+                let fix = ctx.rename_node(&path, &format!("{base}@{address:x}"));
+                report.node_with_fix(&path, message, fix);
+            }
+        }
+    }
+}
+
+/// `reg` consists of one or more (address, size) pairs, sized per the parent node.
+struct RegFormat;
+
+impl Check for RegFormat {
+    fn name(&self) -> &'static str {
+        "reg_format"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            let Some(reg) = node.get_property("reg") else {
+                continue;
+            };
+            let Some(parent) = ctx.parent(&path) else {
+                report.property(&path, "reg", "root node has a reg property");
+                continue;
+            };
+            let (ac, sc) = (parent.address_cells(), parent.size_cells());
+            let cells = (ac as usize).checked_add(sc as usize);
+            let Some(entry_len) = cells.and_then(|cells| cells.checked_mul(4)) else {
+                let message =
+                    format!("invalid cell count (#address-cells == {ac}, #size-cells == {sc})");
+                report.property(&path, "reg", message);
+                continue;
+            };
+            if reg.is_empty() || entry_len == 0 || !reg.len().is_multiple_of(entry_len) {
+                let n = reg.len();
+                let message = format!(
+                    "reg property has invalid length ({n} bytes) \
+                     (#address-cells == {ac}, #size-cells == {sc})"
+                );
+                report.property(&path, "reg", message);
+            }
+        }
+    }
+}
+
+/// Nodes with `reg` or `ranges` should not rely on the default cell sizes, which are
+/// surprising and differ between implementations.
+struct AvoidDefaultAddrSize;
+
+impl Check for AvoidDefaultAddrSize {
+    fn name(&self) -> &'static str {
+        "avoid_default_addr_size"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            let Some(parent) = ctx.parent(&path) else {
+                continue;
+            };
+            if node.get_property("reg").is_none() && node.get_property("ranges").is_none() {
+                continue;
+            }
+            if parent.get_property("#address-cells").is_none() {
+                report.node(&path, "relying on default #address-cells value");
+            }
+            if parent.get_property("#size-cells").is_none() {
+                report.node(&path, "relying on default #size-cells value");
+            }
+        }
+    }
+}
+
+/// The `reg` regions of enabled devices do not overlap, unless one device contains the other, as
+/// a bus or multi-function device may.  Memory is checked by memory_overlap.
+struct RegOverlap;
+
+impl Check for RegOverlap {
+    fn name(&self) -> &'static str {
+        "reg_overlap"
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        let memory = |path: &NodePath| {
+            let top = path.segments().next().unwrap_or("");
+            top == "reserved-memory" || top.split('@').next() == Some("memory")
+        };
+        let map: Vec<_> = memory_map(ctx.tree)
+            .into_iter()
+            .filter(|e| e.enabled && e.region.size != 0 && !memory(&e.path))
+            .collect();
+        let mut reported = HashSet::new();
+        for (i, a) in map.iter().enumerate() {
+            let end = a.region.address.saturating_add(a.region.size);
+            for b in map[i + 1..].iter().take_while(|b| b.region.address < end) {
+                if a.path.starts_with(&b.path) || b.path.starts_with(&a.path) {
+                    continue;
+                }
+                if reported.insert((a.path.clone(), b.path.clone())) {
+                    let Region { address, size } = b.region;
+                    let message =
+                        format!("region {address:#x} (size {size:#x}) overlaps {}", a.path);
+                    report.property(&b.path, "reg", message);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_reg() {
+    let source = r#"
+/dts-v1/;
+/ {
+    #address-cells = <2>;
+    #size-cells = <1>;
+    memory@80000000 { reg = <0 0x80000000 0x1000>; };
+    mmio@1,0 { reg = <1 0 0x1000>; };
+    wrong@100 { reg = <0 0x200 0x10>; };
+    short@0 { reg = <0 0 0 0>; };
+    nounit { reg = <0 0 1>; };
+    noreg@1 { };
+    bus@0 {
+        ranges;
+        dev@0 { reg = <0 1>; };
+    };
+    a@10000 { reg = <0 0x10000 0x100>; };
+    b@10080 { reg = <0 0x10080 0x100>; };
+    huge {
+        #address-cells = <0xffffffff>;
+        #size-cells = <1>;
+        dev@0 { reg = <0 1>; };
+    };
+};
+"#;
+    let mut registry = Registry::empty();
+    register(&mut registry);
+    let (warnings, errors) = super::run_on_source(source, &registry);
+    assert_eq!(
+        warnings,
+        [
+            "8: (unit_address_vs_reg) /wrong@100: unit address does not match reg address (expected \"200\")",
+            "10: (unit_address_vs_reg) /nounit: node has a reg or ranges property, but no unit name",
+            "11: (unit_address_vs_reg) /noreg@1: node has a unit name, but no reg or ranges property",
+            "9: (reg_format) /short@0/reg: reg property has invalid length (16 bytes) (#address-cells == 2, #size-cells == 1)",
+            "14: (reg_format) /bus@0/dev@0/reg: reg property has invalid length (8 bytes) (#address-cells == 2, #size-cells == 1)",
+            "21: (reg_format) /huge/dev@0/reg: reg property has invalid length (8 bytes) (#address-cells == 4294967295, #size-cells == 1)",
+            "14: (avoid_default_addr_size) /bus@0/dev@0: relying on default #address-cells value",
+            "14: (avoid_default_addr_size) /bus@0/dev@0: relying on default #size-cells value",
+            "17: (reg_overlap) /b@10080/reg: region 0x10080 (size 0x100) overlaps /a@10000",
+        ]
+    );
+    assert!(errors.is_empty());
+
+    // A region may end at the top of a 128-bit address space.
+    let source = r#"
+/dts-v1/;
+/ {
+    #address-cells = <4>;
+    #size-cells = <4>;
+    a@ffffffffffffffffffffffffffffff00 {
+        reg = <0xffffffff 0xffffffff 0xffffffff 0xffffff00 0 0 0 0x1000>;
+    };
+    b@ffffffffffffffffffffffffffffff80 {
+        reg = <0xffffffff 0xffffffff 0xffffffff 0xffffff80 0 0 0 0x10>;
+    };
+};
+"#;
+    let (warnings, errors) = super::run_on_source(source, &registry);
+    assert_eq!(
+        warnings,
+        [
+            "10: (reg_overlap) /b@ffffffffffffffffffffffffffffff80/reg: region 0xffffffffffffffffffffffffffffff80 (size 0x10) overlaps /a@ffffffffffffffffffffffffffffff00",
+        ]
+    );
+    assert!(errors.is_empty());
+}
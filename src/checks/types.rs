@@ -0,0 +1,83 @@
+//! Checks that standard properties have the expected types.
+
+use super::{Check, Context, Registry, Reporter};
+use crate::BinaryNode;
+
+pub(super) fn register(registry: &mut Registry) {
+    for (name, property) in [
+        ("device_type_is_string", "device_type"),
+        ("model_is_string", "model"),
+        ("status_is_string", "status"),
+        ("label_is_string", "label"),
+    ] {
+        registry.register(Box::new(PropertyType {
+            name,
+            property,
+            kind: Kind::String,
+        }));
+    }
+    registry.register(Box::new(PropertyType {
+        name: "compatible_is_string_list",
+        property: "compatible",
+        kind: Kind::StringList,
+    }));
+    for (name, property) in [
+        ("address_cells_is_cell", "#address-cells"),
+        ("size_cells_is_cell", "#size-cells"),
+        ("interrupt_cells_is_cell", "#interrupt-cells"),
+    ] {
+        registry.register(Box::new(PropertyType {
+            name,
+            property,
+            kind: Kind::Cell,
+        }));
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    String,
+    StringList,
+    Cell,
+}
+
+impl Kind {
+    fn matches(self, node: &BinaryNode, property: &str) -> bool {
+        match self {
+            Kind::String => node.get_string(property).is_some(),
+            Kind::StringList => node.get_strings(property).is_some(),
+            Kind::Cell => node.get_u32(property).is_some(),
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Kind::String => "a string",
+            Kind::StringList => "a string list",
+            Kind::Cell => "a single cell",
+        }
+    }
+}
+
+/// Every instance of `property` must have the type `kind`.
+struct PropertyType {
+    name: &'static str,
+    property: &'static str,
+    kind: Kind,
+}
+
+impl Check for PropertyType {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            if node.get_property(self.property).is_some() && !self.kind.matches(node, self.property)
+            {
+                let expected = self.kind.describe();
+                report.property(&path, self.property, format!("property is not {expected}"));
+            }
+        }
+    }
+}
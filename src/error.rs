@@ -13,6 +13,8 @@ pub struct SourceError {
     /// The source's address range; effectively, a &'static str which can only be compared by
     /// identity.  This is used to reconstruct the source path during error reporting.
     pub buffer: Range<usize>,
+    /// Suggested edits which would fix the problem.
+    pub fixes: Vec<Edit>,
 }
 
 impl SourceError {
@@ -21,7 +23,12 @@ impl SourceError {
         let buffer = span.get_input().as_bytes().as_ptr_range();
         let buffer = buffer.start as usize..buffer.end as usize;
         let pest_error = Box::new(Error::new_from_span(message, span));
-        Self { pest_error, buffer }
+        let fixes = vec![];
+        Self {
+            pest_error,
+            buffer,
+            fixes,
+        }
     }
 
     // TODO: better to do this with an enum field.  the formatting looks strange this way.
@@ -32,7 +39,12 @@ impl SourceError {
         let buffer = buffer.start as usize..buffer.end as usize;
         let pos = pest::Position::from_start(src);
         let pest_error = Box::new(Error::new_from_pos(message, pos));
-        Self { pest_error, buffer }
+        let fixes = vec![];
+        Self {
+            pest_error,
+            buffer,
+            fixes,
+        }
     }
 
     pub fn path(&self) -> Option<&str> {
@@ -47,6 +59,37 @@ impl SourceError {
     pub fn buffer(&self) -> Range<*const u8> {
         self.buffer.start as *const u8..self.buffer.end as *const u8
     }
+
+    pub fn with_fix(mut self, fix: Edit) -> Self {
+        self.fixes.push(fix);
+        self
+    }
+}
+
+/// A replacement of some source text, suggested to fix a problem.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Edit {
+    /// The source's address range, as in `SourceError`.
+    pub buffer: Range<usize>,
+    /// The replaced range, in bytes from the start of the source.
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl Edit {
+    /// Replace the text of `span` with `replacement`.
+    pub fn new(span: pest::Span, replacement: impl Into<String>) -> Self {
+        let input = span.get_input().as_bytes().as_ptr_range();
+        Self {
+            buffer: input.start as usize..input.end as usize,
+            range: span.start()..span.end(),
+            replacement: replacement.into(),
+        }
+    }
+
+    pub fn buffer(&self) -> Range<*const u8> {
+        self.buffer.start as *const u8..self.buffer.end as *const u8
+    }
 }
 
 impl From<Error<Rule>> for SourceError {
@@ -54,7 +97,12 @@ impl From<Error<Rule>> for SourceError {
         let pest_error = Box::new(pest_error);
         let buffer = "".as_bytes().as_ptr_range();
         let buffer = buffer.start as usize..buffer.end as usize;
-        SourceError { pest_error, buffer }
+        let fixes = vec![];
+        SourceError {
+            pest_error,
+            buffer,
+            fixes,
+        }
     }
 }
 
@@ -105,6 +153,30 @@ impl Scribe {
         self.errors.push(err);
     }
 
+    /// The number of warnings and errors logged so far.
+    pub fn count(&self) -> usize {
+        self.warnings.len() + self.errors.len()
+    }
+
+    /// Whether any errors have been logged, including warnings treated as errors.
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// The edits suggested to fix the logged warnings and errors.
+    pub fn fixes(&self) -> Vec<Edit> {
+        let diagnostics = self.warnings.iter().chain(&self.errors);
+        diagnostics.flat_map(|e| e.fixes.clone()).collect()
+    }
+
+    /// The messages of the logged warnings and errors, without their locations.
+    pub fn messages(&self) -> Vec<String> {
+        let diagnostics = self.warnings.iter().chain(&self.errors);
+        diagnostics
+            .map(|e| e.pest_error.variant.message().into_owned())
+            .collect()
+    }
+
     /// Extract the logged warnings and errors.
     pub fn into_inner(mut self) -> (Vec<SourceError>, Vec<SourceError>) {
         let mut warnings = vec![];
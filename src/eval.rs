@@ -1,5 +1,6 @@
 //! Facilities for evaluating expressions and phandle references in a devicetree.
 
+use crate::cache::{BuildCache, Digest};
 use crate::error::{Scribe, SourceError};
 use crate::fs::Loader;
 use crate::label::{LabelMap, LabelResolver};
@@ -10,9 +11,8 @@ use crate::{Arena, BinaryNode, SourceNode};
 use core::str::CharIndices;
 use hashlink::{LinkedHashMap, LinkedHashSet};
 use std::borrow::Cow;
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::cell::RefCell;
 
 /// Assigns phandles and evaluates expressions.
 /// Call with the output of `crate::merge::merge()` or `resolve_incbin_paths()`.
@@ -21,6 +21,18 @@ pub fn eval(
     node_labels: LabelMap,
     loader: &impl Loader,
     scribe: &mut Scribe,
+) -> BinaryNode {
+    eval_cached(tree, node_labels, loader, None, scribe)
+}
+
+/// Like `eval()`, but reuse the property values of nodes found in `cache`, and add those of the
+/// other nodes.  See `crate::cache` for what a node's values are keyed on.
+pub fn eval_cached(
+    tree: SourceNode,
+    node_labels: LabelMap,
+    loader: &impl Loader,
+    cache: Option<&mut BuildCache>,
+    scribe: &mut Scribe,
 ) -> BinaryNode {
     let phandles = assign_phandles(&tree, &node_labels, scribe);
     let read_file = |path: &Path| match loader.read(path.to_owned()) {
@@ -29,7 +41,7 @@ pub fn eval(
             "can't load file {path:?}"
         ))),
     };
-    let mut tree = evaluate_expressions(tree, &node_labels, &phandles, read_file, scribe);
+    let mut tree = evaluate_expressions(tree, &node_labels, &phandles, read_file, cache, scribe);
     // poke assigned phandle values into the final tree
     for (path, phandle) in phandles {
         tree.walk_mut(path.segments())
@@ -39,6 +51,26 @@ pub fn eval(
     tree
 }
 
+/// Evaluate the address and size of each `/memreserve/` entry in `dts`.
+pub fn memreserves<'a>(dts: &Dts<'a>) -> Result<Vec<(pest::Span<'a>, u64, u64)>, SourceError> {
+    type NoLookup = fn(&PropertyReference) -> Result<Vec<u8>, SourceError>;
+    let mut entries = vec![];
+    for top_def in dts.top_def {
+        let TopDef::Memreserve(memreserve) = top_def else {
+            continue;
+        };
+        let eval = |arg: &MemreserveArg| match arg {
+            MemreserveArg::ParenExpr(x) => x.eval(None::<&NoLookup>),
+            MemreserveArg::IntLiteral(x) => x.eval(None::<&NoLookup>),
+        };
+        let [address, size] = memreserve.memreserve_arg else {
+            unreachable!("the grammar requires two arguments");
+        };
+        entries.push((*memreserve.span(), eval(address)?, eval(size)?));
+    }
+    Ok(entries)
+}
+
 fn path_from_bytes(bytes: &[u8]) -> PathBuf {
     #[cfg(not(unix))]
     return PathBuf::from(String::from_utf8_lossy(&bytes).into_owned());
@@ -259,46 +291,168 @@ fn visit_node_phandles<P>(
     }
 }
 
-fn eval_property_reference(
-    loc: &NodePath,
-    labels: &LabelResolver<&Prop>,
-    phandles: &LinkedHashMap<NodePath, u32>,
-    read_file: &impl Fn(&Path) -> Result<Vec<u8>, SourceError>,
-    propref: &PropertyReference,
-    visited: &RefCell<HashSet<usize>>,
-) -> Result<Vec<u8>, SourceError> {
-    let (nodepath, prop) = labels.prop_from_prop_ref(loc, propref)?;
-
-    // Detect cycles
-    let key = &raw const *prop as usize;
-    if !visited.borrow_mut().insert(key) {
-        return Err(propref.err("property reference cycle detected"));
+fn display_prop_key(path: &NodePath, name: &str) -> String {
+    if path.is_root() {
+        format!("/{name}")
+    } else {
+        format!("{path}/{name}")
     }
+}
 
-    let Some(propvalue) = prop.prop_value else {
-        return Ok(vec![]);
-    };
+/// Where a property reference leads.
+enum Target {
+    /// index into the list of vertices
+    Prop(usize),
+    /// The reference does not name an existing property.
+    Unresolved,
+    /// Following the reference closes a cycle, described by the chain of property names.
+    Cycle(String),
+}
 
-    // Reuse the lookup rules from `evaluate_expressions`
-    let lookup_label = |nr: &NodeReference| labels.resolve(&nodepath, nr);
-    let lookup_phandle = |nr: &NodeReference| Ok(*phandles.get(&labels.resolve(&nodepath, nr)?).unwrap());
-    let lookup_property = |pr: &PropertyReference| {
-        // Recurse to resolve nested property references
-        eval_property_reference(&nodepath, labels, phandles, read_file, pr, visited)
-    };
+/// One vertex of the property reference graph:  a property, and the properties it references.
+struct PropVertex<'a> {
+    /// index into the list of node paths
+    node: usize,
+    name: &'a str,
+    prop: &'a Prop<'a>,
+    /// Target of each property reference within the value.
+    refs: Vec<(&'a PropertyReference<'a>, Target)>,
+}
 
-    let result = evaluate_propvalue(
-        propvalue,
-        lookup_label,
-        lookup_phandle,
-        lookup_property,
-        |p| read_file(p),
-    );
+/// Collect every property of `node` in the order visited by `Node::map_values()`.
+fn collect_properties<'a>(
+    node: &'a SourceNode<'a>,
+    path: NodePath,
+    nodes: &mut Vec<NodePath>,
+    out: &mut Vec<PropVertex<'a>>,
+) {
+    let index = nodes.len();
+    for (name, prop) in node.properties() {
+        out.push(PropVertex {
+            node: index,
+            name,
+            prop,
+            refs: vec![],
+        });
+    }
+    nodes.push(path);
+    for (name, child) in node.children() {
+        let child_path = nodes[index].join(name);
+        collect_properties(child, child_path, nodes, out);
+    }
+}
 
-    // Remove the visited key from the set now that we're done evaluating it
-    visited.borrow_mut().remove(&key);
+/// Order the vertices so that each property follows all the properties it references.
+/// Each edge that closes a cycle is replaced by `Target::Cycle`; it is only an error if
+/// evaluation follows it, which it may not if the reference is in an untaken ternary branch.
+fn topological_order(nodes: &[NodePath], vertices: &mut [PropVertex]) -> Vec<usize> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        New,
+        Active,
+        Done,
+    }
+    let mut marks = vec![Mark::New; vertices.len()];
+    let mut order = Vec::with_capacity(vertices.len());
+    // An explicit stack of (vertex, next edge to follow); deep reference chains are plausible
+    // in generated trees, so avoid recursion.
+    let mut stack: Vec<(usize, usize)> = vec![];
+    for start in 0..vertices.len() {
+        if marks[start] != Mark::New {
+            continue;
+        }
+        marks[start] = Mark::Active;
+        stack.push((start, 0));
+        while let Some((v, edge)) = stack.last_mut() {
+            let (v, e) = (*v, *edge);
+            let Some((_, target)) = vertices[v].refs.get(e) else {
+                marks[v] = Mark::Done;
+                order.push(v);
+                stack.pop();
+                continue;
+            };
+            *edge += 1;
+            let &Target::Prop(w) = target else {
+                continue;
+            };
+            match marks[w] {
+                Mark::New => {
+                    marks[w] = Mark::Active;
+                    stack.push((w, 0));
+                }
+                Mark::Active => {
+                    let first = stack.iter().position(|&(u, _)| u == w).unwrap();
+                    let chain: Vec<String> = stack[first..]
+                        .iter()
+                        .chain(Some(&(w, 0)))
+                        .map(|&(u, _)| display_prop_key(&nodes[vertices[u].node], vertices[u].name))
+                        .collect();
+                    vertices[v].refs[e].1 = Target::Cycle(chain.join(" -> "));
+                }
+                Mark::Done => (),
+            }
+        }
+    }
+    // Vertices on a cycle were still appended to `order` once their edges were exhausted, and
+    // every remaining edge leads to a vertex earlier in the order.
+    order
+}
 
-    result
+/// Digest of everything the values of one node's properties depend on:  their source text, and
+/// the paths, phandles and files they refer to.  Returns `None` if the node uses property
+/// references, whose targets are not covered, or if a reference fails to resolve.
+fn node_key<P>(
+    props: &[PropVertex],
+    loc: &NodePath,
+    labels: &LabelResolver<P>,
+    phandles: &PhandleMap,
+    read_file: impl Fn(&Path) -> Result<Vec<u8>, SourceError>,
+) -> Option<Digest> {
+    let mut key = vec![];
+    let mut put = |bytes: &[u8]| {
+        key.extend((bytes.len() as u64).to_be_bytes());
+        key.extend(bytes);
+    };
+    // Overflow is handled differently, depending on this feature.
+    put(&[cfg!(feature = "wrapping-arithmetic") as u8]);
+    for vertex in props {
+        if !vertex.refs.is_empty() {
+            return None;
+        }
+        put(vertex.name.as_bytes());
+        let Some(propvalue) = vertex.prop.prop_value else {
+            put(&[0]);
+            continue;
+        };
+        put(&[1]);
+        put(propvalue.str().as_bytes());
+        for labeled_value in propvalue.labeled_value {
+            match labeled_value.value {
+                Value::Cells(cells) => {
+                    for label_or_cell in cells.label_or_cell {
+                        if let LabelOrCell::Cell(Cell::NodeReference(noderef)) = label_or_cell {
+                            let target = labels.resolve(loc, noderef).ok()?;
+                            put(&phandles.get(&target)?.to_be_bytes());
+                        }
+                    }
+                }
+                Value::NodeReference(noderef) => {
+                    let target = labels.resolve(loc, noderef).ok()?;
+                    put(target.display().as_bytes());
+                }
+                Value::Incbin(incbin) => {
+                    // The path was rewritten by `resolve_incbin_paths()`, so `str()` may not show it.
+                    let path_bytes = incbin.incbin_args.quoted_string.unescape().ok()?;
+                    put(&path_bytes);
+                    put(&crate::cache::digest(
+                        &read_file(&path_from_bytes(&path_bytes)).ok()?,
+                    ));
+                }
+                _ => (),
+            }
+        }
+    }
+    Some(crate::cache::digest(&key))
 }
 
 fn evaluate_expressions(
@@ -306,39 +460,126 @@ fn evaluate_expressions(
     node_labels: &LabelMap,
     phandles: &PhandleMap,
     read_file: impl Fn(&Path) -> Result<Vec<u8>, SourceError>,
+    mut cache: Option<&mut BuildCache>,
     scribe: &mut Scribe,
 ) -> BinaryNode {
-    let old = root.clone();
-    let labels = &LabelResolver(node_labels, &old);
-    let read_file = |p: &Path| read_file(p);
-    let mut eval = |loc: &NodePath, prop: &Prop| match prop.prop_value {
-        None => vec![],
-        Some(propvalue) => {
-            let lookup_label = |noderef: &NodeReference| labels.resolve(loc, noderef);
-            let lookup_phandle = |noderef: &NodeReference| {
-                Ok(*phandles.get(&labels.resolve(loc, noderef)?).unwrap())
+    let mut nodes = vec![];
+    let mut vertices = vec![];
+    collect_properties(&root, NodePath::root(), &mut nodes, &mut vertices);
+    let index: HashMap<(&NodePath, &str), usize> = vertices
+        .iter()
+        .enumerate()
+        .map(|(i, v)| ((&nodes[v.node], v.name), i))
+        .collect();
+
+    // Build the property reference graph.  Resolution errors are reported during evaluation,
+    // and only for references that are actually followed.
+    let labels = &LabelResolver(node_labels, &root);
+    for vertex in vertices.iter_mut() {
+        let Some(propvalue) = vertex.prop.prop_value else {
+            continue;
+        };
+        let mut proprefs = vec![];
+        propvalue.property_references(&mut proprefs);
+        for propref in proprefs {
+            let target = match labels.prop_from_prop_ref(&nodes[vertex.node], propref) {
+                Ok((path, name, _)) => Target::Prop(index[&(&path, name)]),
+                Err(_) => Target::Unresolved,
             };
-            let lookup_prop = |propref: &PropertyReference| {
-                eval_property_reference(loc, labels, phandles, &read_file, propref, &RefCell::new(HashSet::new()))
+            vertex.refs.push((propref, target));
+        }
+    }
+
+    // Reuse the values of nodes with an unchanged key.  The properties of each node are adjacent.
+    let mut values: Vec<Option<Vec<u8>>> = vec![None; vertices.len()];
+    let mut missed = vec![];
+    if let Some(cache) = cache.as_deref_mut() {
+        let mut start = 0;
+        for props in vertices.chunk_by(|a, b| a.node == b.node) {
+            let range = start..start + props.len();
+            start = range.end;
+            let loc = &nodes[props[0].node];
+            let Some(key) = node_key(props, loc, labels, phandles, &read_file) else {
+                continue;
             };
-            match evaluate_propvalue(
-                propvalue,
-                lookup_label,
-                lookup_phandle,
-                lookup_prop,
-                read_file,
-            ) {
-                Ok(v) => v,
-                Err(e) => {
-                    scribe.err(e);
-                    // TODO:  Consider returning `node::Node<Result<Vec<u8>, SourceError>>`
-                    // instead of this in-band signaling.
-                    b"<ERROR>\0".to_vec()
+            match cache.node_values(&key) {
+                Some(cached) => {
+                    for (value, cached) in values[range].iter_mut().zip(cached) {
+                        *value = Some(cached.clone());
+                    }
                 }
+                None => missed.push((key, range)),
             }
         }
-    };
-    root.map_located_values(&NodePath::root(), &mut eval)
+    }
+
+    // Evaluate each other property once, after everything it references.
+    let order = topological_order(&nodes, &mut vertices);
+    for i in order {
+        if values[i].is_some() {
+            continue;
+        }
+        let vertex = &vertices[i];
+        let loc = &nodes[vertex.node];
+        let Some(propvalue) = vertex.prop.prop_value else {
+            values[i] = Some(vec![]);
+            continue;
+        };
+        // Set when a referenced property failed to evaluate; that failure was already reported.
+        let dependency_failed = core::cell::Cell::new(false);
+        let lookup_label = |noderef: &NodeReference| labels.resolve(loc, noderef);
+        let lookup_phandle =
+            |noderef: &NodeReference| Ok(*phandles.get(&labels.resolve(loc, noderef)?).unwrap());
+        let lookup_prop = |propref: &PropertyReference| {
+            let (_, target) = vertex
+                .refs
+                .iter()
+                .find(|(r, _)| core::ptr::eq(*r, propref))
+                .unwrap();
+            match target {
+                &Target::Prop(t) => values[t].clone().ok_or_else(|| {
+                    dependency_failed.set(true);
+                    propref.err("referenced property failed to evaluate")
+                }),
+                Target::Unresolved => match labels.prop_from_prop_ref(loc, propref) {
+                    Err(e) => Err(e),
+                    Ok(_) => unreachable!(),
+                },
+                Target::Cycle(chain) => {
+                    Err(propref.err(format!("property reference cycle detected: {chain}")))
+                }
+            }
+        };
+        match evaluate_propvalue(
+            propvalue,
+            lookup_label,
+            lookup_phandle,
+            lookup_prop,
+            &read_file,
+        ) {
+            Ok(v) => values[i] = Some(v),
+            Err(_) if dependency_failed.get() => (),
+            Err(e) => scribe.err(e),
+        }
+    }
+
+    if let Some(cache) = cache {
+        for (key, range) in missed {
+            if let Some(node_values) = values[range].iter().cloned().collect() {
+                cache.insert_node_values(key, node_values);
+            }
+        }
+    }
+
+    // TODO:  Consider returning `node::Node<Result<Vec<u8>, SourceError>>`
+    // instead of this in-band signaling.
+    let mut values = values.into_iter();
+    root.map_values(&mut |_| {
+        values
+            .next()
+            .unwrap()
+            .unwrap_or_else(|| b"<ERROR>\0".to_vec())
+    })
 }
 
 // TODO:  Accept Scribe here as well.  It's probably not useful to report more than one error, or
@@ -463,7 +704,7 @@ fn evaluate_propvalue(
     Ok(r)
 }
 
-trait UnescapeExt<'a> {
+pub(crate) trait UnescapeExt<'a> {
     fn unescape(&self) -> Result<Cow<'a, [u8]>, SourceError>;
 }
 
@@ -625,7 +866,11 @@ impl<T: Fn(&PropertyReference) -> Result<Vec<u8>, SourceError>> EvalExt<T> for T
             return Ok(left);
         };
         // Note that subexpression evaluation is lazy, unlike dtc.
-        if left != 0 { mid.eval(lookup_property) } else { right.eval(lookup_property) }
+        if left != 0 {
+            mid.eval(lookup_property)
+        } else {
+            right.eval(lookup_property)
+        }
     }
 }
 
@@ -681,7 +926,7 @@ impl<T: Fn(&PropertyReference) -> Result<Vec<u8>, SourceError>> EvalExt<T> for U
                         "property reference returned {n} bytes, need 4 or 8 | bytes={bytes:?}"
                     ))),
                 }
-            },
+            }
         }
     }
 }
@@ -731,6 +976,91 @@ fn eval_binary_op(left: u64, op: &str, right: u64) -> Result<u64, &'static str>
     }
 }
 
+/// Collect the property references within a value or expression, in source order.
+pub(crate) trait PropertyReferencesExt<'a> {
+    fn property_references(&self, out: &mut Vec<&'a PropertyReference<'a>>);
+}
+
+impl<'a> PropertyReferencesExt<'a> for PropValue<'a> {
+    fn property_references(&self, out: &mut Vec<&'a PropertyReference<'a>>) {
+        for labeled_value in self.labeled_value {
+            match labeled_value.value {
+                Value::Cells(cells) => {
+                    for label_or_cell in cells.label_or_cell {
+                        match label_or_cell {
+                            LabelOrCell::Cell(Cell::PropertyReference(propref)) => {
+                                out.push(propref)
+                            }
+                            LabelOrCell::Cell(Cell::ParenExpr(expr)) => {
+                                expr.property_references(out)
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                Value::PropertyReference(propref) => out.push(propref),
+                _ => (),
+            }
+        }
+    }
+}
+
+impl<'a> PropertyReferencesExt<'a> for ParenExpr<'a> {
+    fn property_references(&self, out: &mut Vec<&'a PropertyReference<'a>>) {
+        self.expr.property_references(out);
+    }
+}
+
+impl<'a> PropertyReferencesExt<'a> for Expr<'a> {
+    fn property_references(&self, out: &mut Vec<&'a PropertyReference<'a>>) {
+        self.ternary_prec.property_references(out);
+    }
+}
+
+impl<'a> PropertyReferencesExt<'a> for TernaryPrec<'a> {
+    fn property_references(&self, out: &mut Vec<&'a PropertyReference<'a>>) {
+        self.logical_or_prec.property_references(out);
+        // Both branches are included, although evaluation visits only one.
+        for expr in self.expr {
+            expr.property_references(out);
+        }
+    }
+}
+
+macro_rules! impl_binary_property_references {
+    ($rule:ident, $arg:ident) => {
+        impl<'a> PropertyReferencesExt<'a> for $rule<'a> {
+            fn property_references(&self, out: &mut Vec<&'a PropertyReference<'a>>) {
+                for arg in self.$arg {
+                    arg.property_references(out);
+                }
+            }
+        }
+    };
+}
+
+impl_binary_property_references!(LogicalOrPrec, logical_and_prec);
+impl_binary_property_references!(LogicalAndPrec, bitwise_or_prec);
+impl_binary_property_references!(BitwiseOrPrec, bitwise_xor_prec);
+impl_binary_property_references!(BitwiseXorPrec, bitwise_and_prec);
+impl_binary_property_references!(BitwiseAndPrec, equal_prec);
+impl_binary_property_references!(EqualPrec, compare_prec);
+impl_binary_property_references!(ComparePrec, shift_prec);
+impl_binary_property_references!(ShiftPrec, add_prec);
+impl_binary_property_references!(AddPrec, mul_prec);
+impl_binary_property_references!(MulPrec, unary_prec);
+
+impl<'a> PropertyReferencesExt<'a> for UnaryPrec<'a> {
+    fn property_references(&self, out: &mut Vec<&'a PropertyReference<'a>>) {
+        match self {
+            UnaryPrec::UnaryExpr(x) => x.unary_prec.property_references(out),
+            UnaryPrec::ParenExpr(x) => x.property_references(out),
+            UnaryPrec::IntLiteral(_) => (),
+            UnaryPrec::PropertyReference(x) => out.push(x),
+        }
+    }
+}
+
 #[test]
 fn test_property_reference_cycles() {
     const CYCLE_TEST1: &str = r#"
@@ -740,23 +1070,57 @@ fn test_property_reference_cycles() {
 }; }; "#;
 
     const CYCLE_TEST2: &str = r#"
-/ { loop {
-    loops_back_to_b = ${loops_back_to_a};
-    loops_back_to_a = ${loops_back_to_b};
-}; }; "#;
+/ {
+    a = <${/b}>;
+    b = <(${/c} + 1)>;
+    c = ${/a};
+    d = ${/c};
+}; "#;
 
-    for source in [CYCLE_TEST1, CYCLE_TEST2] {
+    for (source, chain) in [
+        (
+            CYCLE_TEST1,
+            "/loop/loops_back_to_b -> /loop/loops_back_to_a -> /loop/loops_back_to_b",
+        ),
+        (CYCLE_TEST2, "/a -> /b -> /c -> /a"),
+    ] {
         let loader = crate::fs::DummyLoader;
         let arena = crate::Arena::new();
         let dts = crate::parse::parse_typed(source, &arena).unwrap();
         let mut scribe = Scribe::new(true);
         let (tree, node_labels, _, _) = crate::merge::merge(dts, &mut scribe);
         _ = eval(tree, node_labels, &loader, &mut scribe);
-        let err = scribe.collect().err().unwrap();
-        assert!(err.to_string().contains("property reference cycle detected"));
+        let (_, errors) = scribe.into_inner();
+        // Properties which merely depend on a cycle are not reported separately.
+        let [err] = &errors[..] else {
+            panic!("expected one error, got {errors:?}");
+        };
+        let message = err.to_string();
+        assert!(message.contains("property reference cycle detected"));
+        assert!(message.contains(chain), "missing {chain:?} in:\n{message}");
     }
 }
 
+#[test]
+fn test_untaken_branch_references() {
+    // References in the branch a ternary does not take are neither resolved nor followed.
+    const SOURCE: &str = r#"
+/ {
+    a = <(1 ? 2 : ${/nope})>;
+    b = <(0 ? ${/b} : 3)>;
+}; "#;
+    let loader = crate::fs::DummyLoader;
+    let arena = crate::Arena::new();
+    let dts = crate::parse::parse_typed(SOURCE, &arena).unwrap();
+    let mut scribe = Scribe::new(true);
+    let (tree, node_labels, _, _) = crate::merge::merge(dts, &mut scribe);
+    let tree = eval(tree, node_labels, &loader, &mut scribe);
+    let (_, errors) = scribe.into_inner();
+    assert!(errors.is_empty(), "unexpected errors: {errors:?}");
+    assert_eq!(tree.get_property("a").unwrap(), &[0, 0, 0, 2]);
+    assert_eq!(tree.get_property("b").unwrap(), &[0, 0, 0, 3]);
+}
+
 #[test]
 fn test_eval() {
     for source in [
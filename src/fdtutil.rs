@@ -0,0 +1,378 @@
+//! Support for the `fdtget` and `fdtput` tools:  finding nodes of a DTB by path or alias, and
+//! converting property values to and from text according to a type specifier such as `-t hx`.
+
+use crate::BinaryNode;
+use crate::path::NodePath;
+use core::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// `s`:  a list of NUL-terminated strings
+    String,
+    /// `i`:  signed integers
+    Signed,
+    /// `u`:  unsigned decimal integers
+    Unsigned,
+    /// `x`:  unsigned hexadecimal integers
+    Hex,
+    /// `r`:  the bytes of the value, unchanged
+    Raw,
+}
+
+/// A type specifier:  an optional element size (`hh` or `b` for bytes, `h` for 16 bits, `l` for
+/// 32 bits), followed by a `Kind`, as in `s`, `i`, `bx` or `hx`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ValueType {
+    pub kind: Kind,
+    /// The size of each integer in bytes, if given.
+    pub size: Option<usize>,
+}
+
+impl FromStr for ValueType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (size, kind) = [("hh", 1), ("b", 1), ("h", 2), ("l", 4)]
+            .into_iter()
+            .find_map(|(prefix, size)| Some((Some(size), s.strip_prefix(prefix)?)))
+            .unwrap_or((None, s));
+        let kind = match kind {
+            "s" => Kind::String,
+            "i" => Kind::Signed,
+            "u" => Kind::Unsigned,
+            "x" => Kind::Hex,
+            "r" => Kind::Raw,
+            _ => {
+                return Err(format!(
+                    "invalid type {s:?}; expected a size (hh, b, h or l) and then s, i, u, x or r"
+                ));
+            }
+        };
+        Ok(ValueType { kind, size })
+    }
+}
+
+/// Resolve `path` to the path of a node, which may not exist.  A path not starting with '/'
+/// begins with the name of an alias, as in `serial0` or `i2c1/eeprom@50`.
+pub fn resolve_path(tree: &BinaryNode, path: &str) -> Result<NodePath, String> {
+    if path.starts_with('/') {
+        return Ok(NodePath::root().join(path));
+    }
+    let (alias, rest) = path.split_once('/').unwrap_or((path, ""));
+    let aliases = tree.get_child("aliases");
+    let Some(target) = aliases.and_then(|a| a.get_string(alias)) else {
+        return Err(format!("{path}: no alias {alias}"));
+    };
+    if !target.starts_with('/') {
+        return Err(format!("{path}: alias {alias} is not an absolute path"));
+    }
+    Ok(NodePath::root().join(target).join(rest))
+}
+
+/// Find the node at `path`, as interpreted by `resolve_path()`.
+pub fn find_node<'a>(tree: &'a BinaryNode, path: &str) -> Result<&'a BinaryNode, String> {
+    let resolved = resolve_path(tree, path)?;
+    tree.walk(resolved.segments())
+        .ok_or_else(|| format!("{path}: no such node"))
+}
+
+/// The node at `path`, as interpreted by `resolve_path()`.  If it is missing, create it if
+/// `create` is set, and create its missing ancestors if `auto_path` is set.
+pub fn node_mut<'a>(
+    tree: &'a mut BinaryNode,
+    path: &str,
+    create: bool,
+    auto_path: bool,
+) -> Result<&'a mut BinaryNode, String> {
+    let resolved = resolve_path(tree, path)?;
+    let segments: Vec<&str> = resolved.segments().collect();
+    let mut node = tree;
+    for (i, segment) in segments.iter().enumerate() {
+        let last = i == segments.len() - 1;
+        if node.get_child(segment).is_none() && !(auto_path || create && last) {
+            return Err(format!("{path}: no such node"));
+        }
+        node = node.add_child(segment);
+    }
+    Ok(node)
+}
+
+/// Create the node at `path`, and its missing ancestors if `auto_path` is set.
+pub fn create_node(tree: &mut BinaryNode, path: &str, auto_path: bool) -> Result<(), String> {
+    if find_node(tree, path).is_ok() {
+        return Err(format!("{path}: node already exists"));
+    }
+    node_mut(tree, path, true, auto_path)?;
+    Ok(())
+}
+
+/// Remove the node at `path` and its subnodes.
+pub fn remove_node(tree: &mut BinaryNode, path: &str) -> Result<(), String> {
+    let resolved = resolve_path(tree, path)?;
+    if resolved.is_root() {
+        return Err("cannot remove the root node".into());
+    }
+    find_node(tree, path)?;
+    let parent = tree.walk_mut(resolved.parent().segments()).unwrap();
+    parent.remove_child(resolved.leaf());
+    Ok(())
+}
+
+/// Delete the properties `names` of the node at `path`, each of which must exist.
+pub fn delete_properties(
+    tree: &mut BinaryNode,
+    path: &str,
+    names: &[String],
+) -> Result<(), String> {
+    let node = node_mut(tree, path, false, false)?;
+    for name in names {
+        if node.remove_property(name).is_none() {
+            return Err(format!("{path}: no property {name}"));
+        }
+    }
+    Ok(())
+}
+
+/// Format `value` as text.  Without a type, values which look like strings are shown as strings,
+/// and others as signed integers of 32 bits if they divide evenly, or else of bytes.  Strings and
+/// integers are separated by spaces, and bytes in hex have two digits.
+pub fn format_value(value: &[u8], value_type: Option<ValueType>) -> Result<Vec<u8>, String> {
+    let value_type = value_type.unwrap_or(ValueType {
+        kind: match is_printable_strings(value) {
+            true => Kind::String,
+            false => Kind::Signed,
+        },
+        size: None,
+    });
+    match value_type.kind {
+        Kind::Raw => return Ok(value.to_vec()),
+        Kind::String => {
+            let Some(strings) = value.strip_suffix(&[0]) else {
+                return Err("value is not a NUL-terminated string".into());
+            };
+            let spaced = strings.iter().map(|&b| if b == 0 { b' ' } else { b });
+            return Ok(spaced.collect());
+        }
+        Kind::Signed | Kind::Unsigned | Kind::Hex => (),
+    }
+    let size = value_type.size.unwrap_or(match value.len() % 4 {
+        0 => 4,
+        _ => 1,
+    });
+    if !value.len().is_multiple_of(size) {
+        return Err(format!(
+            "value of {} bytes is not a multiple of the element size ({size})",
+            value.len()
+        ));
+    }
+    let integers: Vec<String> = value
+        .chunks_exact(size)
+        .map(|bytes| {
+            let n = bytes.iter().fold(0u64, |n, &b| n << 8 | b as u64);
+            match value_type.kind {
+                Kind::Signed => {
+                    let shift = 64 - 8 * size;
+                    (((n << shift) as i64) >> shift).to_string()
+                }
+                Kind::Hex if size == 1 => format!("{n:02x}"),
+                Kind::Hex => format!("{n:x}"),
+                _ => n.to_string(),
+            }
+        })
+        .collect();
+    Ok(integers.join(" ").into_bytes())
+}
+
+/// Whether `value` is one or more nonempty NUL-terminated strings of printable ASCII.
+fn is_printable_strings(value: &[u8]) -> bool {
+    let Some(strings) = value.strip_suffix(&[0]) else {
+        return false;
+    };
+    strings
+        .split(|&b| b == 0)
+        .all(|s| !s.is_empty() && s.iter().all(|b| (b' '..=b'~').contains(b)))
+}
+
+/// Encode `args` as a property value.  Integers are 32 bits unless the type gives a size.
+pub fn encode_value(args: &[String], value_type: ValueType) -> Result<Vec<u8>, String> {
+    let mut value = vec![];
+    if value_type.kind == Kind::String {
+        for arg in args {
+            value.extend_from_slice(arg.as_bytes());
+            value.push(0);
+        }
+        return Ok(value);
+    }
+    if value_type.kind == Kind::Raw {
+        return Err("raw values cannot be encoded; use -t bx to give bytes in hex".into());
+    }
+    let size = value_type.size.unwrap_or(4);
+    let bits = 8 * size as u32;
+    let min = match value_type.kind {
+        Kind::Signed => -(1i128 << (bits - 1)),
+        _ => 0,
+    };
+    for arg in args {
+        let Some(n) = parse_integer(arg, value_type.kind) else {
+            return Err(format!("invalid integer {arg:?}"));
+        };
+        if n < min || n >= 1i128 << bits {
+            return Err(format!("{arg} does not fit in {size} bytes"));
+        }
+        value.extend_from_slice(&(n as u64).to_be_bytes()[8 - size..]);
+    }
+    Ok(value)
+}
+
+/// Parse an integer as C's `scanf()` would for the conversion `%i`, `%u` or `%x`.
+fn parse_integer(s: &str, kind: Kind) -> Option<i128> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) if kind == Kind::Signed => (true, digits),
+        _ => (false, s),
+    };
+    let hex = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"));
+    let (radix, digits) = match (kind, hex) {
+        (Kind::Hex, _) => (16, hex.unwrap_or(digits)),
+        (Kind::Signed, Some(hex)) => (16, hex),
+        (Kind::Signed, None) if digits.len() > 1 && digits.starts_with('0') => (8, &digits[1..]),
+        _ => (10, digits),
+    };
+    if digits.is_empty() || digits.starts_with(['+', '-']) {
+        return None;
+    }
+    let n = u64::from_str_radix(digits, radix).ok()? as i128;
+    Some(if negative { -n } else { n })
+}
+
+#[test]
+fn test_fdtutil() {
+    let t = |s: &str| s.parse::<ValueType>().unwrap();
+    assert_eq!(
+        t("bx"),
+        ValueType {
+            kind: Kind::Hex,
+            size: Some(1)
+        }
+    );
+    assert_eq!(t("hhi").size, Some(1));
+    assert_eq!(
+        t("s"),
+        ValueType {
+            kind: Kind::String,
+            size: None
+        }
+    );
+    assert!("q".parse::<ValueType>().is_err());
+    assert!("hs1".parse::<ValueType>().is_err());
+
+    let format = |value: &[u8], value_type: Option<&str>| {
+        let value_type = value_type.map(|s| s.parse().unwrap());
+        String::from_utf8(format_value(value, value_type).unwrap()).unwrap()
+    };
+    assert_eq!(
+        format(b"arm,pl011\0arm,primecell\0", None),
+        "arm,pl011 arm,primecell"
+    );
+    assert_eq!(format(&[0, 0, 0, 1, 0xff, 0xff, 0xff, 0xff], None), "1 -1");
+    assert_eq!(
+        format(&[0, 0, 0, 1, 0xff, 0xff, 0xff, 0xff], Some("u")),
+        "1 4294967295"
+    );
+    assert_eq!(format(&[0x12, 0x34, 0xab], None), "18 52 -85");
+    assert_eq!(format(&[0x12, 0x34, 0xab, 0xcd], Some("hx")), "1234 abcd");
+    assert_eq!(format(&[0x12, 0x34, 0xab, 0xcd], Some("bx")), "12 34 ab cd");
+    assert_eq!(format(&[1, 0x6e, 0x36, 0], Some("bx")), "01 6e 36 00");
+    assert_eq!(format(b"", None), "");
+    assert!(format_value(&[1, 2, 3], Some(t("hx"))).is_err());
+
+    let encode = |args: &[&str], value_type: &str| {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        encode_value(&args, t(value_type))
+    };
+    assert_eq!(encode(&["a", "bc"], "s"), Ok(b"a\0bc\0".to_vec()));
+    assert_eq!(
+        encode(&["1", "-1", "0x10", "010"], "i"),
+        Ok(vec![
+            0, 0, 0, 1, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0x10, 0, 0, 0, 8
+        ])
+    );
+    assert_eq!(encode(&["ab", "0xcd"], "bx"), Ok(vec![0xab, 0xcd]));
+    assert_eq!(encode(&["65535"], "hu"), Ok(vec![0xff, 0xff]));
+    assert_eq!(
+        encode(&["65536"], "hu"),
+        Err("65536 does not fit in 2 bytes".into())
+    );
+    assert_eq!(encode(&["-1"], "u"), Err("invalid integer \"-1\"".into()));
+
+    let mut tree = BinaryNode::default();
+    let aliases = tree.add_child("aliases");
+    aliases.set_property("i2c1", b"/soc/i2c@1000\0".to_vec());
+    aliases.set_property("bad", b"soc\0".to_vec());
+    tree.add_child("soc")
+        .add_child("i2c@1000")
+        .add_child("eeprom@50");
+    assert_eq!(
+        resolve_path(&tree, "i2c1/eeprom@50"),
+        Ok(NodePath::root().join("soc/i2c@1000/eeprom@50"))
+    );
+    assert!(find_node(&tree, "i2c1").is_ok());
+    assert_eq!(
+        find_node(&tree, "/soc/x").err(),
+        Some("/soc/x: no such node".into())
+    );
+    assert_eq!(
+        resolve_path(&tree, "spi0"),
+        Err("spi0: no alias spi0".into())
+    );
+    assert_eq!(
+        resolve_path(&tree, "bad/x"),
+        Err("bad/x: alias bad is not an absolute path".into())
+    );
+
+    assert_eq!(
+        node_mut(&mut tree, "/a/b", false, false).err(),
+        Some("/a/b: no such node".into())
+    );
+    node_mut(&mut tree, "/a/b", false, true)
+        .unwrap()
+        .set_property("x", vec![]);
+    assert!(
+        find_node(&tree, "/a/b")
+            .unwrap()
+            .get_property("x")
+            .is_some()
+    );
+    assert_eq!(
+        create_node(&mut tree, "/c/d", false),
+        Err("/c/d: no such node".into())
+    );
+    create_node(&mut tree, "i2c1/rtc@68", false).unwrap();
+    assert!(find_node(&tree, "/soc/i2c@1000/rtc@68").is_ok());
+    assert_eq!(
+        create_node(&mut tree, "/a/b", true),
+        Err("/a/b: node already exists".into())
+    );
+    assert_eq!(
+        remove_node(&mut tree, "/"),
+        Err("cannot remove the root node".into())
+    );
+    assert_eq!(
+        remove_node(&mut tree, "/a/c"),
+        Err("/a/c: no such node".into())
+    );
+    remove_node(&mut tree, "/a").unwrap();
+    assert!(find_node(&tree, "/a/b").is_err());
+    let names = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        delete_properties(&mut tree, "/aliases", &names(&["bad", "spi0"])),
+        Err("/aliases: no property spi0".into())
+    );
+    assert!(
+        find_node(&tree, "/aliases")
+            .unwrap()
+            .get_property("bad")
+            .is_none()
+    );
+}
@@ -41,17 +41,31 @@ impl Display for DeserializeError {
 
 impl core::error::Error for DeserializeError {}
 
+/// A DTB's tree, with the memory reservations and header fields stored alongside it.
+#[derive(Default)]
+pub struct Fdt {
+    pub root: BinaryNode,
+    /// The address and size of each memory reservation, as given by `/memreserve/`.
+    pub reservations: Vec<(u64, u64)>,
+    pub boot_cpuid_phys: u32,
+}
+
 /// Parse a DTB.
 pub fn deserialize(blob: &[u8]) -> Result<BinaryNode, DeserializeError> {
+    Ok(deserialize_fdt(blob)?.root)
+}
+
+/// Parse a DTB, keeping its memory reservations.
+pub fn deserialize_fdt(blob: &[u8]) -> Result<Fdt, DeserializeError> {
     let mut header = blob;
     let magic = header.read_u32()?;
     let totalsize = header.read_u32()? as usize;
     let off_dt_struct = header.read_u32()? as usize;
     let off_dt_strings = header.read_u32()? as usize;
-    let _off_dt_rsvmap = header.read_u32()? as usize;
+    let off_mem_rsvmap = header.read_u32()? as usize;
     let version = header.read_u32()?;
     let last_comp_version = header.read_u32()?;
-    let _boot_cpuid_phys = header.read_u32()?;
+    let boot_cpuid_phys = header.read_u32()?;
     let size_dt_strings = header.read_u32()? as usize;
     let size_dt_struct = header.read_u32()? as usize;
     if magic != FDT_MAGIC {
@@ -63,7 +77,7 @@ pub fn deserialize(blob: &[u8]) -> Result<BinaryNode, DeserializeError> {
     if version < 17 || last_comp_version > 17 {
         return Err("unsupported version".into());
     }
-    if off_dt_struct > totalsize || off_dt_strings > totalsize {
+    if off_dt_struct > totalsize || off_dt_strings > totalsize || off_mem_rsvmap > totalsize {
         return Err("invalid header".into());
     }
     if size_dt_struct > totalsize - off_dt_struct || size_dt_strings > totalsize - off_dt_strings {
@@ -72,6 +86,21 @@ pub fn deserialize(blob: &[u8]) -> Result<BinaryNode, DeserializeError> {
     let mut dt_struct = &blob[off_dt_struct..off_dt_struct + size_dt_struct];
     let dt_strings = &blob[off_dt_strings..off_dt_strings + size_dt_strings];
 
+    let mut rsvmap = &blob[off_mem_rsvmap..totalsize];
+    let mut reservations = vec![];
+    loop {
+        let entry = (rsvmap.read_u64()?, rsvmap.read_u64()?);
+        if entry == (0, 0) {
+            break;
+        }
+        reservations.push(entry);
+    }
+    let fdt = |root| Fdt {
+        root,
+        reservations,
+        boot_cpuid_phys,
+    };
+
     let node = match dt_struct.read_token()? {
         FdtToken::BeginNode => {
             // discard the name of the root node
@@ -82,14 +111,14 @@ pub fn deserialize(blob: &[u8]) -> Result<BinaryNode, DeserializeError> {
         FdtToken::End => {
             // Given "/delete-node/ &{/};", `dtc` will produce a DTB with no root node.
             // Treat that as an empty root node.
-            return Ok(BinaryNode::default());
+            return Ok(fdt(BinaryNode::default()));
         }
         _ => return Err("unexpected start token".into()),
     };
     if dt_struct.read_token()? != FdtToken::End {
         return Err("missing end token".into());
     }
-    Ok(node)
+    Ok(fdt(node))
 }
 
 fn deserialize_node(stream: &mut &[u8], strtab: &[u8]) -> Result<BinaryNode, DeserializeError> {
@@ -117,9 +146,21 @@ fn deserialize_node(stream: &mut &[u8], strtab: &[u8]) -> Result<BinaryNode, Des
     }
 }
 
-/// Construct a DTB.
-/// Memory reservations are not supported.
+/// Construct a DTB with no memory reservations.
 pub fn serialize(root: &BinaryNode) -> Vec<u8> {
+    serialize_parts(root, &[], 0)
+}
+
+/// Construct a DTB, including its memory reservations.
+pub fn serialize_fdt(fdt: &Fdt) -> Vec<u8> {
+    serialize_parts(&fdt.root, &fdt.reservations, fdt.boot_cpuid_phys)
+}
+
+fn serialize_parts(
+    root: &BinaryNode,
+    reservations: &[(u64, u64)],
+    boot_cpuid_phys: u32,
+) -> Vec<u8> {
     let mut out = Vec::<u8>::new();
     let mut strings = StringTable::default();
 
@@ -144,11 +185,15 @@ pub fn serialize(root: &BinaryNode) -> Vec<u8> {
     out.write_u32(FDT_HEADER_SIZE as u32);
     out.write_u32(17);
     out.write_u32(16);
-    out.write_u32(0);
+    out.write_u32(boot_cpuid_phys);
     out.write_u32(0); // size_dt_strings not yet known
     out.write_u32(0); // size_dt_struct not yet known
 
     // memory reservations block
+    for &(address, size) in reservations {
+        out.write_u64(address);
+        out.write_u64(size);
+    }
     out.write_u64(0);
     out.write_u64(0);
 
@@ -259,6 +304,7 @@ impl StringTable {
 
 trait FdtReader {
     fn read_u32(&mut self) -> Result<u32, DeserializeError>;
+    fn read_u64(&mut self) -> Result<u64, DeserializeError>;
     fn read_token(&mut self) -> Result<FdtToken, DeserializeError>;
     fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, DeserializeError>;
     fn read_cstr(&mut self) -> Result<String, DeserializeError>;
@@ -276,6 +322,15 @@ impl FdtReader for &[u8] {
         }
     }
 
+    fn read_u64(&mut self) -> Result<u64, DeserializeError> {
+        if let Some((first, rest)) = self.split_first_chunk::<8>() {
+            *self = rest;
+            Ok(u64::from_be_bytes(*first))
+        } else {
+            Err(DeserializeError::Truncated)
+        }
+    }
+
     fn read_token(&mut self) -> Result<FdtToken, DeserializeError> {
         FdtToken::from_u32(self.read_u32()?).ok_or("invalid FDT token".into())
     }
@@ -320,3 +375,26 @@ impl FdtReader for &[u8] {
         Ok(())
     }
 }
+
+#[test]
+fn test_flat() {
+    let mut root = BinaryNode::default();
+    root.add_child("chosen")
+        .set_property("bootargs", b"console=ttyS0\0".to_vec());
+    let fdt = Fdt {
+        root,
+        reservations: vec![(0x8000_0000, 0x1000), (0x1_0000_0000, 0x20)],
+        boot_cpuid_phys: 1,
+    };
+    let blob = serialize_fdt(&fdt);
+    let parsed = deserialize_fdt(&blob).unwrap();
+    assert_eq!(parsed.reservations, fdt.reservations);
+    assert_eq!(parsed.boot_cpuid_phys, 1);
+    assert_eq!(serialize_fdt(&parsed), blob);
+    assert_eq!(
+        deserialize_fdt(&serialize(&parsed.root))
+            .unwrap()
+            .reservations,
+        []
+    );
+}
@@ -124,6 +124,15 @@ impl LocalFileLoader {
         }
     }
 
+    /// Serve `contents` as the file at `path` instead of reading it, as when compiling edited
+    /// sources before writing them.
+    pub fn insert(&mut self, path: PathBuf, contents: Vec<u8>) {
+        self.file_contents
+            .get_mut()
+            .unwrap()
+            .insert(path, Some(contents));
+    }
+
     fn track_parent_of_missing(&self, mut path: &Path) {
         let mut parents_of_missing = self.parents_of_missing.lock().unwrap();
         while let Some(parent) = path.parent() {
@@ -215,12 +224,122 @@ impl Loader for LocalFileLoader {
 
     fn path_of_buffer(&self, mem: Range<*const u8>) -> Option<PathBuf> {
         for (path, bytes) in self.file_contents.lock().unwrap().iter() {
-            if let Some(bytes) = bytes {
-                if bytes.as_ptr_range() == mem {
-                    return Some(path.clone());
-                }
+            if let Some(bytes) = bytes
+                && bytes.as_ptr_range() == mem
+            {
+                return Some(path.clone());
             }
         }
         None
     }
 }
+
+/// A request made of a `Loader`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Access {
+    /// `Loader::find(relative_to, included_path)`
+    Find(PathBuf, PathBuf),
+    /// `Loader::read(path)`
+    Read(PathBuf),
+}
+
+impl Access {
+    /// Repeat this request against `loader`.
+    pub fn replay<'l>(&self, loader: &'l impl Loader) -> Option<(&'l Path, &'l [u8])> {
+        match self {
+            Access::Find(relative_to, included_path) => loader.find(relative_to, included_path),
+            Access::Read(path) => loader.read(path.clone()),
+        }
+    }
+}
+
+/// The outcome of an `Access`:  the path found and its contents, or `None`.
+pub type AccessResult<'l> = Option<(&'l Path, &'l [u8])>;
+
+/// Wraps another loader (typically one shared by several compilations) and records the requests
+/// made through this wrapper.  This identifies the inputs of a single compilation.
+pub struct TrackingLoader<'l, L> {
+    inner: &'l L,
+    log: Mutex<Vec<(Access, AccessResult<'l>)>>,
+}
+
+impl<'l, L: Loader> TrackingLoader<'l, L> {
+    pub fn new(inner: &'l L) -> Self {
+        Self {
+            inner,
+            log: Default::default(),
+        }
+    }
+
+    /// The requests made so far, in order, with their results.
+    pub fn accesses(&self) -> Vec<(Access, AccessResult<'l>)> {
+        self.log.lock().unwrap().clone()
+    }
+
+    /// Return the requests made so far, and clear the record.
+    pub fn take_accesses(&self) -> Vec<(Access, AccessResult<'l>)> {
+        core::mem::take(&mut *self.log.lock().unwrap())
+    }
+
+    fn record(&self, access: Access, result: AccessResult<'l>) -> AccessResult<'l> {
+        self.log.lock().unwrap().push((access, result));
+        result
+    }
+}
+
+impl<L: Loader> Loader for TrackingLoader<'_, L> {
+    fn find(&self, relative_to: &Path, included_path: &Path) -> Option<(&Path, &[u8])> {
+        let access = Access::Find(relative_to.into(), included_path.into());
+        self.record(access, self.inner.find(relative_to, included_path))
+    }
+
+    fn read(&self, path: PathBuf) -> Option<(&Path, &[u8])> {
+        let access = Access::Read(path.clone());
+        self.record(access, self.inner.read(path))
+    }
+
+    fn positive_deps(&self) -> Vec<PathBuf> {
+        let mut deps: Vec<_> = self
+            .log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(_, result)| result.map(|(path, _)| path.to_owned()))
+            .collect();
+        deps.sort_unstable();
+        deps.dedup();
+        deps
+    }
+
+    /// This is not specific to the requests made through this wrapper; the inner loader does not
+    /// report which directories each search visited.
+    fn negative_deps(&self) -> Vec<PathBuf> {
+        self.inner.negative_deps()
+    }
+
+    fn path_of_buffer(&self, mem: Range<*const u8>) -> Option<PathBuf> {
+        self.inner.path_of_buffer(mem)
+    }
+}
+
+/// Replace the file at `path` with `contents`, so that readers see either the old file or the new
+/// one and never a partial write.  The data is written to a temporary file in the same directory,
+/// named uniquely so that concurrent writers do not clobber each other, and then renamed.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let tmp = temp_path(path);
+    let result = std::fs::write(&tmp, contents).and_then(|()| std::fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// A path in the same directory as `path`, not used by any other call in any process.
+pub fn temp_path(path: &Path) -> PathBuf {
+    static COUNTER: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.{n}.tmp", std::process::id()));
+    path.with_file_name(name)
+}
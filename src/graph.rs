@@ -0,0 +1,126 @@
+//! The OF graph:  connections between devices, such as a display controller and a panel,
+//! described by `port` and `endpoint` nodes whose `remote-endpoint` properties name each other.
+//!
+//! A device's ports are its children named `port` or `port@N`, or children of its `ports`
+//! node.  Each port contains endpoints named `endpoint` or `endpoint@M`.
+
+use crate::BinaryNode;
+use crate::path::NodePath;
+
+/// One endpoint of the graph.
+pub struct Endpoint {
+    pub path: NodePath,
+    /// The device owning the endpoint's port.
+    pub device: NodePath,
+    /// The port's `reg`, or 0 if it has none.
+    pub port: u32,
+    /// The endpoint's `reg`, or 0 if it has none.
+    pub endpoint: u32,
+    /// The endpoint named by `remote-endpoint`, if any.
+    pub remote: Option<Remote>,
+}
+
+pub enum Remote {
+    Endpoint(NodePath),
+    /// `remote-endpoint` is malformed or names no node.
+    Invalid,
+}
+
+/// The node name before any '@'.
+pub fn base_name(path: &NodePath) -> &str {
+    path.leaf().split('@').next().unwrap()
+}
+
+/// The device owning the port at `port`.
+pub fn device_of_port(port: &NodePath) -> NodePath {
+    let parent = port.parent();
+    match base_name(&parent) {
+        "ports" => parent.parent(),
+        _ => parent,
+    }
+}
+
+/// Find every endpoint in the tree, in tree order.
+pub fn endpoints(tree: &BinaryNode) -> Vec<Endpoint> {
+    let phandles = tree.phandles();
+    let mut endpoints = vec![];
+    tree.for_each_node(&NodePath::root(), &mut |path, node| {
+        if path.is_root() || base_name(path) != "endpoint" {
+            return;
+        }
+        let port_path = path.parent();
+        if base_name(&port_path) != "port" {
+            return;
+        }
+        let port = tree.walk(port_path.segments()).unwrap();
+        let remote = node.get_property("remote-endpoint").map(|_| {
+            let target = node
+                .get_u32("remote-endpoint")
+                .and_then(|p| phandles.get(&p));
+            match target {
+                Some(target) => Remote::Endpoint(target.clone()),
+                None => Remote::Invalid,
+            }
+        });
+        endpoints.push(Endpoint {
+            path: path.clone(),
+            device: device_of_port(&port_path),
+            port: port.get_u32("reg").unwrap_or(0),
+            endpoint: node.get_u32("reg").unwrap_or(0),
+            remote,
+        });
+    });
+    endpoints
+}
+
+/// Describe each endpoint and what it is connected to, one per line.
+pub fn format(tree: &BinaryNode) -> String {
+    use core::fmt::Write;
+    let endpoints = endpoints(tree);
+    let describe = |path: &NodePath| match endpoints.iter().find(|e| e.path == *path) {
+        Some(e) => format!("{} port {} endpoint {}", e.device, e.port, e.endpoint),
+        None => format!("{path} (not an endpoint)"),
+    };
+    let mut out = String::new();
+    for e in &endpoints {
+        let remote = match &e.remote {
+            Some(Remote::Endpoint(path)) => describe(path),
+            Some(Remote::Invalid) => "(invalid remote-endpoint)".into(),
+            None => "(unconnected)".into(),
+        };
+        _ = writeln!(out, "{} -> {remote}", describe(&e.path));
+    }
+    out
+}
+
+#[test]
+fn test_graph() {
+    let source = r#"
+/dts-v1/;
+/ {
+    dsi {
+        ports {
+            #address-cells = <1>;
+            #size-cells = <0>;
+            port@0 { reg = <0>; dsi_in: endpoint { }; };
+            port@1 { reg = <1>; dsi_out: endpoint { remote-endpoint = <&panel_in>; }; };
+        };
+    };
+    panel {
+        port { panel_in: endpoint { remote-endpoint = <&dsi_out>; }; };
+    };
+};
+"#;
+    let arena = crate::Arena::new();
+    let dts = crate::parse::parse_typed(source, &arena).unwrap();
+    let mut scribe = crate::error::Scribe::new(true);
+    let (tree, labels, _, _) = crate::merge::merge(dts, &mut scribe);
+    let tree = crate::eval::eval(tree, labels, &crate::fs::DummyLoader, &mut scribe);
+    assert!(scribe.report(&crate::fs::DummyLoader, &mut std::io::stderr()));
+    assert_eq!(
+        format(&tree),
+        "/dsi port 0 endpoint 0 -> (unconnected)\n\
+         /dsi port 1 endpoint 0 -> /panel port 0 endpoint 0\n\
+         /panel port 0 endpoint 0 -> /dsi port 1 endpoint 0\n"
+    );
+}
@@ -0,0 +1,327 @@
+//! Interrupt resolution:  following each interrupt of a device from its interrupt parent,
+//! through any nexus nodes' `interrupt-map`, to the interrupt controller which receives it.
+//!
+//! At a nexus, the interrupt's unit address (the first `#address-cells` of the device's `reg`)
+//! and specifier are masked by `interrupt-map-mask` and matched against the child side of each
+//! `interrupt-map` entry; the parent side of the matching entry gives the next hop.
+
+use crate::BinaryNode;
+use crate::path::NodePath;
+use std::collections::HashMap;
+
+/// An interrupt as received by its controller.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Interrupt {
+    pub controller: NodePath,
+    /// The specifier as the controller interprets it, of its `#interrupt-cells`.
+    pub specifier: Vec<u32>,
+    /// The nexus nodes which mapped the interrupt, in order.
+    pub nexuses: Vec<NodePath>,
+}
+
+/// One entry of an `interrupt-map`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MapEntry {
+    pub child_address: Vec<u32>,
+    pub child_specifier: Vec<u32>,
+    pub parent: NodePath,
+    pub parent_address: Vec<u32>,
+    pub parent_specifier: Vec<u32>,
+}
+
+/// Nexus nodes may map to further nexus nodes, but not endlessly.
+const MAX_NEXUSES: usize = 16;
+
+/// Resolves interrupts in an evaluated tree, finding nodes by phandle.
+pub struct InterruptResolver<'a>(pub &'a HashMap<u32, NodePath>, pub &'a BinaryNode);
+
+impl<'a> InterruptResolver<'a> {
+    fn node(&self, path: &NodePath) -> Result<&'a BinaryNode, String> {
+        self.1
+            .walk(path.segments())
+            .ok_or_else(|| format!("no node {path}"))
+    }
+
+    /// Find the node with the given phandle.
+    pub fn phandle_target(&self, phandle: u32) -> Option<(&'a NodePath, &'a BinaryNode)> {
+        let path = self.0.get(&phandle)?;
+        Some((path, self.1.walk(path.segments())?))
+    }
+
+    /// Find the interrupt parent of the node at `path`:  the target of its `interrupt-parent`
+    /// property, or else its parent if that is an interrupt controller or nexus, or else the
+    /// interrupt parent of its parent.
+    pub fn interrupt_parent(&self, path: &NodePath) -> Result<(NodePath, &'a BinaryNode), String> {
+        let mut p = path.clone();
+        loop {
+            let node = self.node(&p)?;
+            if node.get_property("interrupt-parent").is_some() {
+                let Some(phandle) = node.get_u32("interrupt-parent") else {
+                    return Err(format!("interrupt-parent of {p} is not a single cell"));
+                };
+                let Some((parent, node)) = self.phandle_target(phandle) else {
+                    return Err(format!("interrupt-parent of {p} is not a valid phandle"));
+                };
+                return Ok((parent.clone(), node));
+            }
+            if p.is_root() {
+                return Err("no interrupt-parent found".into());
+            }
+            p = p.parent();
+            let node = self.node(&p)?;
+            if is_interrupt_target(node) {
+                return Ok((p, node));
+            }
+        }
+    }
+
+    /// Decode the `interrupt-map` of the nexus at `path`.
+    pub fn map_entries(&self, path: &NodePath) -> Result<Vec<MapEntry>, String> {
+        let node = self.node(path)?;
+        let Some(map) = node.get_cells("interrupt-map") else {
+            return Err(format!(
+                "{path} has no interrupt-map, or it is not a list of cells"
+            ));
+        };
+        let Some(child_interrupt_cells) = node.get_u32("#interrupt-cells") else {
+            return Err(format!("{path} has no #interrupt-cells"));
+        };
+        let Some(child_address_cells) = node.get_u32("#address-cells") else {
+            return Err(format!("{path} has no #address-cells"));
+        };
+        let mut entries = vec![];
+        let mut rest = &map[..];
+        while !rest.is_empty() {
+            let entry = entries.len();
+            let truncated = || format!("entry {entry} is truncated");
+            let (child_address, tail) = rest
+                .split_at_checked(child_address_cells as usize)
+                .ok_or_else(truncated)?;
+            let (child_specifier, tail) = tail
+                .split_at_checked(child_interrupt_cells as usize)
+                .ok_or_else(truncated)?;
+            let (&phandle, tail) = tail.split_first().ok_or_else(truncated)?;
+            let Some((parent, parent_node)) = self.phandle_target(phandle) else {
+                return Err(format!("entry {entry}: no node has phandle {phandle:#x}"));
+            };
+            if !is_interrupt_target(parent_node) {
+                return Err(format!(
+                    "entry {entry}: {parent} is not an interrupt controller or nexus"
+                ));
+            }
+            let Some(interrupt_cells) = parent_node.get_u32("#interrupt-cells") else {
+                return Err(format!("entry {entry}: {parent} has no #interrupt-cells"));
+            };
+            // The parent's unit address is absent unless it has #address-cells.
+            let address_cells = parent_node.get_u32("#address-cells").unwrap_or(0);
+            let (parent_address, tail) = tail
+                .split_at_checked(address_cells as usize)
+                .ok_or_else(truncated)?;
+            let (parent_specifier, tail) = tail
+                .split_at_checked(interrupt_cells as usize)
+                .ok_or_else(truncated)?;
+            entries.push(MapEntry {
+                child_address: child_address.to_vec(),
+                child_specifier: child_specifier.to_vec(),
+                parent: parent.clone(),
+                parent_address: parent_address.to_vec(),
+                parent_specifier: parent_specifier.to_vec(),
+            });
+            rest = tail;
+        }
+        Ok(entries)
+    }
+
+    /// Follow an interrupt with unit address `address` and `specifier`, delivered to the node at
+    /// `parent`, through any nexus nodes to its controller.
+    pub fn resolve(
+        &self,
+        parent: &NodePath,
+        address: &[u32],
+        specifier: &[u32],
+    ) -> Result<Interrupt, String> {
+        let mut parent = parent.clone();
+        let mut address = address.to_vec();
+        let mut specifier = specifier.to_vec();
+        let mut nexuses = vec![];
+        loop {
+            let node = self.node(&parent)?;
+            if node.get_property("interrupt-controller").is_some() {
+                return Ok(Interrupt {
+                    controller: parent,
+                    specifier,
+                    nexuses,
+                });
+            }
+            if node.get_property("interrupt-map").is_none() {
+                return Err(format!("{parent} is not an interrupt controller or nexus"));
+            }
+            if nexuses.len() == MAX_NEXUSES {
+                return Err(format!(
+                    "more than {MAX_NEXUSES} interrupt-map lookups; is there a cycle?"
+                ));
+            }
+            let entries = self.map_entries(&parent)?;
+            // A unit address shorter than the nexus's #address-cells is padded with zeros.
+            let address_cells = node.get_u32("#address-cells").unwrap_or(0) as usize;
+            address.resize(address_cells.max(address.len()), 0);
+            let child: Vec<u32> = address[..address_cells]
+                .iter()
+                .chain(&specifier)
+                .copied()
+                .collect();
+            let mask = node
+                .get_cells("interrupt-map-mask")
+                .unwrap_or_else(|| vec![u32::MAX; child.len()]);
+            if mask.len() != child.len() {
+                return Err(format!(
+                    "interrupt-map-mask of {parent} has {} cells, but should have {}",
+                    mask.len(),
+                    child.len()
+                ));
+            }
+            let matches = |e: &&MapEntry| {
+                let entry_child = e.child_address.iter().chain(&e.child_specifier);
+                let masked = child.iter().zip(&mask).map(|(c, m)| c & m);
+                masked.eq(entry_child.copied())
+            };
+            let Some(entry) = entries.iter().find(matches) else {
+                let cells: Vec<String> = child.iter().map(|c| format!("{c:#x}")).collect();
+                return Err(format!(
+                    "interrupt-map of {parent} has no entry for <{}>",
+                    cells.join(" ")
+                ));
+            };
+            nexuses.push(parent);
+            parent = entry.parent.clone();
+            address = entry.parent_address.clone();
+            specifier = entry.parent_specifier.clone();
+        }
+    }
+
+    /// Resolve each interrupt of the device at `path`, given by `interrupts-extended` or else by
+    /// `interrupts`.
+    pub fn interrupts(&self, path: &NodePath) -> Result<Vec<Interrupt>, String> {
+        let node = self.node(path)?;
+        let address = node.get_cells("reg").unwrap_or_default();
+        if node.get_property("interrupts-extended").is_some() {
+            let Some(list) = node.get_cells("interrupts-extended") else {
+                return Err("interrupts-extended is not a list of cells".into());
+            };
+            let mut interrupts = vec![];
+            let mut rest = &list[..];
+            while let Some((&phandle, tail)) = rest.split_first() {
+                let entry = interrupts.len();
+                let Some((parent, parent_node)) = self.phandle_target(phandle) else {
+                    return Err(format!("entry {entry}: no node has phandle {phandle:#x}"));
+                };
+                let Some(cells) = parent_node.get_u32("#interrupt-cells") else {
+                    return Err(format!("entry {entry}: {parent} has no #interrupt-cells"));
+                };
+                let Some((specifier, tail)) = tail.split_at_checked(cells as usize) else {
+                    return Err(format!("entry {entry} is truncated"));
+                };
+                interrupts.push(self.resolve(parent, &address, specifier)?);
+                rest = tail;
+            }
+            return Ok(interrupts);
+        }
+        let Some(list) = node.get_cells("interrupts") else {
+            return Ok(vec![]);
+        };
+        let (parent, parent_node) = self.interrupt_parent(path)?;
+        let Some(cells) = parent_node.get_u32("#interrupt-cells") else {
+            return Err(format!("interrupt parent {parent} has no #interrupt-cells"));
+        };
+        if cells == 0 || !list.len().is_multiple_of(cells as usize) {
+            return Err(format!(
+                "interrupts has {} cells, not a multiple of #interrupt-cells ({cells}) of {parent}",
+                list.len()
+            ));
+        }
+        list.chunks(cells as usize)
+            .map(|specifier| self.resolve(&parent, &address, specifier))
+            .collect()
+    }
+}
+
+/// Whether interrupts may be delivered to `node`.
+pub fn is_interrupt_target(node: &BinaryNode) -> bool {
+    node.get_property("interrupt-controller").is_some()
+        || node.get_property("interrupt-map").is_some()
+}
+
+#[test]
+fn test_interrupts() {
+    let source = r#"
+/dts-v1/;
+/ {
+    interrupt-parent = <&gic>;
+    gic: gic { interrupt-controller; #interrupt-cells = <3>; #address-cells = <0>; };
+    gpio: gpio { interrupt-controller; #interrupt-cells = <2>; };
+    uart { interrupts = <0 5 4>; };
+    key { interrupts-extended = <&gpio 3 1>, <&gic 0 6 4>; };
+    pci: pci {
+        #address-cells = <3>;
+        #size-cells = <2>;
+        #interrupt-cells = <1>;
+        interrupt-map-mask = <0xf800 0 0 7>;
+        interrupt-map = <0x0800 0 0 1 &gic 0 10 4>,
+                        <0x0800 0 0 2 &gic 0 11 4>,
+                        <0x1000 0 0 2 &gic 0 20 4>;
+        dev@1,0 { reg = <0x0800 0 0 0 0>; interrupts = <2>; };
+        bridge: bridge@2,0 {
+            reg = <0x1000 0 0 0 0>;
+            #address-cells = <3>;
+            #size-cells = <2>;
+            #interrupt-cells = <1>;
+            interrupt-map-mask = <0 0 0 7>;
+            interrupt-map = <0 0 0 1 &pci 0x1000 0 0 2>;
+            dev@0,0 { reg = <0 0 0 0 0>; interrupts = <1>; };
+            lost@0,1 { reg = <0x100 0 0 0 0>; interrupts = <3>; };
+        };
+    };
+};
+"#;
+    let arena = crate::Arena::new();
+    let dts = crate::parse::parse_typed(source, &arena).unwrap();
+    let mut scribe = crate::error::Scribe::new(true);
+    let (tree, labels, _, _) = crate::merge::merge(dts, &mut scribe);
+    let tree = crate::eval::eval(tree, labels, &crate::fs::DummyLoader, &mut scribe);
+    assert!(scribe.report(&crate::fs::DummyLoader, &mut std::io::stderr()));
+    let phandles = tree.phandles();
+    let resolver = InterruptResolver(&phandles, &tree);
+    let path = |s: &str| NodePath::root().join(s);
+    let interrupt = |controller: &str, specifier: &[u32], nexuses: &[&str]| Interrupt {
+        controller: path(controller),
+        specifier: specifier.to_vec(),
+        nexuses: nexuses.iter().map(|n| path(n)).collect(),
+    };
+    assert_eq!(
+        resolver.interrupts(&path("uart")),
+        Ok(vec![interrupt("gic", &[0, 5, 4], &[])])
+    );
+    assert_eq!(
+        resolver.interrupts(&path("key")),
+        Ok(vec![
+            interrupt("gpio", &[3, 1], &[]),
+            interrupt("gic", &[0, 6, 4], &[])
+        ])
+    );
+    assert_eq!(
+        resolver.interrupts(&path("pci/dev@1,0")),
+        Ok(vec![interrupt("gic", &[0, 11, 4], &["pci"])])
+    );
+    assert_eq!(
+        resolver.interrupts(&path("pci/bridge@2,0/dev@0,0")),
+        Ok(vec![interrupt(
+            "gic",
+            &[0, 20, 4],
+            &["pci/bridge@2,0", "pci"]
+        )])
+    );
+    assert_eq!(
+        resolver.interrupts(&path("pci/bridge@2,0/lost@0,1")),
+        Err("interrupt-map of /pci/bridge@2,0 has no entry for <0x100 0x0 0x0 0x3>".into())
+    );
+}
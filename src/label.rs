@@ -48,14 +48,19 @@ impl<P> LabelResolver<'_, P> {
         Some(result)
     }
 
-    pub(crate) fn prop_from_prop_ref(
+    /// Resolve a property reference to its node path, property name, and value.
+    pub(crate) fn prop_from_prop_ref<'r>(
         &self,
         relative_to: &NodePath,
-        propref: &PropertyReference,
-    ) -> Result<(NodePath, &P), SourceError> {
+        propref: &PropertyReference<'r>,
+    ) -> Result<(NodePath, &'r str, &P), SourceError> {
         // root.walk() expects all segments to be Node elements, so strip off
         // the property name after the last '/'.
-        let noderef = propref.str().rsplit_once('/').map(|(a, _)| a).unwrap_or(".");
+        let noderef = propref
+            .str()
+            .rsplit_once('/')
+            .map(|(a, _)| a)
+            .unwrap_or(".");
         let nodepath = self
             .resolve_str(relative_to, noderef)
             .ok_or_else(|| propref.err("no such node"))?;
@@ -74,6 +79,6 @@ impl<P> LabelResolver<'_, P> {
             .get_property(propname)
             .ok_or_else(|| propref.err("no such property"))?;
 
-        Ok((nodepath, prop))
+        Ok((nodepath, propname, prop))
     }
 }
@@ -1,7 +1,13 @@
+pub mod address;
+pub mod cache;
+pub mod checks;
 pub mod error;
 pub mod eval;
+pub mod fdtutil;
 pub mod flat;
 pub mod fs;
+pub mod graph;
+pub mod interrupts;
 pub mod label;
 pub mod line;
 pub mod merge;
@@ -9,6 +15,10 @@ pub mod node;
 pub mod parse;
 pub mod path;
 pub mod print;
+pub mod provenance;
+pub mod query;
+#[cfg(feature = "schema")]
+pub mod schema;
 
 pub type Arena = bumpalo::Bump;
 pub type SourceNode<'i> = node::Node<&'i parse::rules::Prop<'i>>;
@@ -21,9 +31,90 @@ pub fn compile(
     scribe: &mut error::Scribe,
 ) -> BinaryNode {
     let dts = parse::parse_concat_with_includes(loader, arena, dts_paths, scribe);
-    let (tree, node_labels, _, _) = merge::merge(&dts, scribe);
+    compile_parsed(loader, arena, &dts, None, None, scribe)
+}
+
+/// Like `compile()`, but also run `checks` over the result.  Checks are skipped if compilation
+/// logged errors, as they would only report the consequences.
+pub fn compile_checked(
+    loader: &impl fs::Loader,
+    arena: &Arena,
+    dts_paths: &[&std::path::Path],
+    checks: &checks::Registry,
+    scribe: &mut error::Scribe,
+) -> BinaryNode {
+    let dts = parse::parse_concat_with_includes(loader, arena, dts_paths, scribe);
+    compile_parsed(loader, arena, &dts, Some(checks), None, scribe)
+}
+
+fn compile_parsed<'a>(
+    loader: &'a impl fs::Loader,
+    arena: &'a Arena,
+    dts: &parse::rules::Dts<'a>,
+    checks: Option<&checks::Registry>,
+    build_cache: Option<&mut cache::BuildCache>,
+    scribe: &mut error::Scribe,
+) -> BinaryNode {
+    let (tree, node_labels, node_changes, prop_changes) = merge::merge(dts, scribe);
     let tree = eval::resolve_incbin_paths(loader, arena, tree, scribe);
-    eval::eval(tree, node_labels, loader, scribe)
+    let binary = eval::eval_cached(tree, node_labels, loader, build_cache, scribe);
+    let Some(checks) = checks else {
+        return binary;
+    };
+    if !scribe.has_errors() {
+        let source = checks::Source {
+            dts,
+            node_changes: &node_changes,
+            prop_changes: &prop_changes,
+        };
+        checks.run(&checks::Context::new(&binary, Some(source)), scribe);
+    }
+    binary
+}
+
+/// Like `compile_checked()`, but reuse files already parsed into `parse_cache`, and reuse a tree
+/// from `build_cache` (if supplied) when none of its inputs have changed and `checks` is
+/// configured alike.  A tree is added to `build_cache` only if compiling it logged no warnings
+/// or errors, so that a cache hit cannot hide a diagnostic.  After an input changes, the tree is
+/// merged again, but nodes whose values are cached are not evaluated again; see `crate::cache`.
+///
+/// The requests made of `loader` are reset at the start of each call; afterward they identify
+/// the inputs of this target, e.g. for `Loader::write_depfile()`.
+pub fn compile_cached<'a, L: fs::Loader>(
+    loader: &'a fs::TrackingLoader<'_, L>,
+    arena: &'a Arena,
+    dts_paths: &[&std::path::Path],
+    parse_cache: &mut parse::ParseCache<'a>,
+    mut build_cache: Option<&mut cache::BuildCache>,
+    checks: &checks::Registry,
+    scribe: &mut error::Scribe,
+) -> BinaryNode {
+    let config = checks.fingerprint();
+    loader.take_accesses();
+    if let Some(tree) = build_cache
+        .as_mut()
+        .and_then(|c| c.get(loader, dts_paths, &config))
+    {
+        return tree;
+    }
+    loader.take_accesses();
+    let diagnostics = scribe.count();
+    let dts =
+        parse::parse_concat_with_includes_cached(loader, arena, dts_paths, parse_cache, scribe);
+    let tree = compile_parsed(
+        loader,
+        arena,
+        &dts,
+        Some(checks),
+        build_cache.as_deref_mut(),
+        scribe,
+    );
+    if let Some(build_cache) = build_cache.as_mut()
+        && scribe.count() == diagnostics
+    {
+        build_cache.insert(dts_paths, &config, &loader.accesses(), &tree);
+    }
+    tree
 }
 
 pub fn compile_result(
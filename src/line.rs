@@ -55,6 +55,7 @@ impl<'a> LineTable<'a> {
 }
 
 #[derive(Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
 struct LCB(u64);
 
 impl LCB {
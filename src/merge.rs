@@ -2,6 +2,7 @@
 
 use crate::SourceNode;
 use crate::error::{Scribe, SourceError};
+use crate::eval::UnescapeExt;
 use crate::label::{LabelMap, LabelResolver};
 use crate::parse::TypedRuleExt;
 use crate::parse::rules::*;
@@ -35,6 +36,17 @@ impl<'a> NodeChange<'a> {
     }
 }
 
+impl<'a> NodeChange<'a> {
+    /// The node's name as written in a definition:  a path, label reference, or node name.
+    pub fn name_span(&self) -> Option<&pest::Span<'a>> {
+        match self {
+            NodeChange::TopNode(x) => Some(x.top_node_name.span()),
+            NodeChange::ChildNode(x) => Some(x.node_name.span()),
+            NodeChange::TopDelNode(_) | NodeChange::DelNode(_) => None,
+        }
+    }
+}
+
 impl<'a> PropChange<'a> {
     pub fn span(&self) -> &pest::Span<'a> {
         match self {
@@ -138,9 +150,70 @@ pub fn merge<'i>(
             }
         }
     }
+    check_name_properties(&mut root, &NodePath::root(), &node_changes, scribe);
     (root, node_labels, node_changes, prop_changes)
 }
 
+/// Like dtc, drop each "name" property which matches the node's base name (the part before
+/// '@'), and reject any other.  Such properties are common in trees converted from Open
+/// Firmware.
+fn check_name_properties(
+    node: &mut SourceNode,
+    path: &NodePath,
+    node_changes: &NodeChanges,
+    scribe: &mut Scribe,
+) {
+    if let Some(prop) = node.get_property("name") {
+        let base_name = path.leaf().split('@').next().unwrap();
+        let value = match prop.prop_value.map(|v| v.labeled_value) {
+            Some(
+                [
+                    LabeledValue {
+                        value: Value::QuotedString(s),
+                        ..
+                    },
+                ],
+            ) => s.unescape().ok(),
+            _ => None,
+        };
+        if value.as_deref() == Some(base_name.as_bytes()) {
+            node.remove_property("name");
+        } else {
+            let found = match (value, prop.prop_value) {
+                (Some(bytes), _) => format!("{:?}", String::from_utf8_lossy(&bytes)),
+                // Any other value is shown as written.
+                (None, Some(v)) => v.str().to_owned(),
+                (None, None) => "an empty value".to_owned(),
+            };
+            let message = format!(
+                "\"name\" property is incorrect ({found} instead of base node name \"{base_name}\")"
+            );
+            // Point at both the node's name and the property, if the node is named earlier in
+            // the same file, as is usual.
+            let prop_span = *prop.span();
+            let named_before = |span: &&pest::Span| {
+                core::ptr::eq(span.get_input(), prop_span.get_input())
+                    && span.start() <= prop_span.start()
+            };
+            let name_span = node_changes.get(path).and_then(|changes| {
+                changes
+                    .iter()
+                    .rev()
+                    .filter_map(|c| c.name_span())
+                    .find(named_before)
+            });
+            let span = match name_span {
+                Some(name_span) => name_span.start_pos().span(&prop_span.end_pos()),
+                None => prop_span,
+            };
+            scribe.err(SourceError::new(message, span));
+        }
+    }
+    for (name, child) in node.children_mut() {
+        check_name_properties(child, &path.join(name), node_changes, scribe);
+    }
+}
+
 fn mark_deleted<'a>(
     node_changes: &mut NodeChanges<'a>,
     prop_changes: &mut PropChanges<'a>,
@@ -221,10 +294,10 @@ fn fill_source_node<'o, 'i: 'o>(
                     .push(NodeChange::ChildNode(childnode));
                 let child = node.add_child(name);
                 for child_node_prefix in childnode.child_node_prefix {
-                    if let ChildNodePrefix::Label(label) = child_node_prefix {
-                        if let Err(e) = add_label(node_labels, label, child, &child_path) {
-                            scribe.err(e);
-                        }
+                    if let ChildNodePrefix::Label(label) = child_node_prefix
+                        && let Err(e) = add_label(node_labels, label, child, &child_path)
+                    {
+                        scribe.err(e);
                     }
                 }
                 let body = childnode.node_body;
@@ -322,3 +395,30 @@ fn test_duplicate_property() {
         "unexpected error:\n{message}"
     );
 }
+
+#[test]
+fn test_name_property() {
+    let source = include_str!("testdata/name_property.dts");
+    let arena = crate::Arena::new();
+    let dts = crate::parse::parse_typed(source, &arena).unwrap();
+    let mut scribe = Scribe::new(false);
+    let (tree, _, _, _) = merge(dts, &mut scribe);
+    let (warnings, errors) = scribe.into_inner();
+    assert!(warnings.is_empty(), "unexpected warnings:\n{warnings:?}");
+    let [err, empty] = &errors[..] else {
+        panic!("expected two errors, got {errors:?}");
+    };
+    let message = format!("{err}");
+    assert!(
+        message.contains("serial@100 {")
+            && message.contains(r#"("uart" instead of base node name "serial")"#),
+        "unexpected error:\n{message}"
+    );
+    let message = format!("{empty}");
+    assert!(
+        message.contains(r#"(an empty value instead of base node name "timer")"#),
+        "unexpected error:\n{message}"
+    );
+    let cpu = tree.walk(["cpu@0"]).unwrap();
+    assert!(cpu.get_property("name").is_none());
+}
@@ -2,6 +2,7 @@ use crate::path::NodePath;
 use core::fmt::{Display, Formatter, Write};
 use hashlink::linked_hash_map::Entry;
 use hashlink::{LinkedHashMap, LinkedHashSet};
+use std::collections::HashMap;
 
 /// An intermediate representation of a node tree, used to gather deletes and overrides.
 /// Preserves the input ordering while allowing random access.
@@ -87,7 +88,7 @@ impl<P> Node<P> {
         self.labels.replace(name.into());
     }
 
-    pub fn labels(&mut self) -> impl Iterator<Item = &String> {
+    pub fn labels(&self) -> impl Iterator<Item = &String> {
         self.labels.iter()
     }
 
@@ -142,6 +143,14 @@ impl<P> Node<P> {
         }
     }
 
+    /// Call `f` with this node and each of its descendants, parents before children.
+    pub fn for_each_node<'s>(&'s self, path: &NodePath, f: &mut impl FnMut(&NodePath, &'s Self)) {
+        f(path, self);
+        for (name, child) in &self.children {
+            child.for_each_node(&path.join(name), f);
+        }
+    }
+
     /// Sort child nodes and properties by name, recursively.
     pub fn sort(&mut self) {
         let mut keys: Vec<String> = self.properties.keys().cloned().collect();
@@ -157,6 +166,67 @@ impl<P> Node<P> {
     }
 }
 
+/// Accessors for property values of the usual types.  These return `None` if the property is
+/// missing or is not well-formed.
+impl Node<Vec<u8>> {
+    /// A property holding a single 32-bit cell.
+    pub fn get_u32(&self, name: &str) -> Option<u32> {
+        let value = self.get_property(name)?;
+        Some(u32::from_be_bytes(value.as_slice().try_into().ok()?))
+    }
+
+    /// A property holding a list of 32-bit cells.
+    pub fn get_cells(&self, name: &str) -> Option<Vec<u32>> {
+        let value = self.get_property(name)?;
+        if !value.len().is_multiple_of(4) {
+            return None;
+        }
+        let cells = value.chunks_exact(4);
+        Some(
+            cells
+                .map(|c| u32::from_be_bytes(c.try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    /// A property holding one or more NUL-terminated UTF-8 strings.
+    pub fn get_strings(&self, name: &str) -> Option<Vec<&str>> {
+        let value = self.get_property(name)?;
+        let s = core::str::from_utf8(value.strip_suffix(&[0])?).ok()?;
+        Some(s.split('\0').collect())
+    }
+
+    /// Map the phandle of each node in this tree to its path.
+    pub fn phandles(&self) -> HashMap<u32, NodePath> {
+        let mut phandles = HashMap::new();
+        self.for_each_node(&NodePath::root(), &mut |path, node| {
+            if let Some(phandle) = node.get_u32("phandle") {
+                phandles.insert(phandle, path.clone());
+            }
+        });
+        phandles
+    }
+
+    /// The number of cells in the addresses of child nodes' `reg` properties, which defaults
+    /// to 2.
+    pub fn address_cells(&self) -> u32 {
+        self.get_u32("#address-cells").unwrap_or(2)
+    }
+
+    /// The number of cells in the sizes of child nodes' `reg` properties, which defaults to 1.
+    pub fn size_cells(&self) -> u32 {
+        self.get_u32("#size-cells").unwrap_or(1)
+    }
+
+    /// A property holding exactly one NUL-terminated UTF-8 string.
+    pub fn get_string(&self, name: &str) -> Option<&str> {
+        match self.get_strings(name)?[..] {
+            [s] => Some(s),
+            _ => None,
+        }
+    }
+}
+
 impl<P> Default for Node<P> {
     fn default() -> Self {
         Self {
@@ -196,7 +266,7 @@ impl OptionDisplay for Vec<u8> {
                 }
                 _ = write!(f, "\"");
             }
-        } else if self.len() % 4 == 0 {
+        } else if self.len().is_multiple_of(4) {
             _ = write!(f, "<");
             for w in self.chunks_exact(4) {
                 // stabilization of slice::array_chunks would simplify this
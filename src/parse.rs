@@ -8,6 +8,7 @@ use core::ops::Range;
 use pest::iterators::Pair;
 use pest::{Parser, Span};
 use rules::{Dts, DtsFile, QuotedString, TopDef};
+use std::collections::HashMap;
 use std::path::Path;
 
 #[derive(pest_derive::Parser, pestle::TypedRules)]
@@ -60,17 +61,61 @@ pub fn parse_concat_with_includes<'a>(
     arena: &'a Arena,
     paths: &[&Path],
     scribe: &mut Scribe,
+) -> Dts<'a> {
+    let mut cache = ParseCache::default();
+    parse_concat_with_includes_cached(loader, arena, paths, &mut cache, scribe)
+}
+
+/// Parsed source files, reusable across compilations which share a loader and an arena.
+///
+/// Entries are keyed on the address of the loader's buffer, as the loader reads each file only
+/// once.  (Two files with equal contents must not share an entry, since errors are attributed
+/// to a file by the address of the buffer they point into.)
+#[derive(Default)]
+pub struct ParseCache<'a> {
+    parsed: HashMap<(usize, usize), &'a Dts<'a>>,
+}
+
+impl<'a> ParseCache<'a> {
+    fn parse(&mut self, source: &'a str, arena: &'a Arena) -> Result<&'a Dts<'a>, SourceError> {
+        let key = source.as_bytes().as_ptr_range();
+        let key = (key.start as usize, key.end as usize);
+        if let Some(dts) = self.parsed.get(&key) {
+            return Ok(dts);
+        }
+        let dts = parse_typed(source, arena)?;
+        self.parsed.insert(key, dts);
+        Ok(dts)
+    }
+
+    /// The number of files parsed.
+    pub fn len(&self) -> usize {
+        self.parsed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parsed.is_empty()
+    }
+}
+
+/// Like `parse_concat_with_includes()`, but reuse any files already parsed into `cache`.
+pub fn parse_concat_with_includes_cached<'a>(
+    loader: &'a impl Loader,
+    arena: &'a Arena,
+    paths: &[&Path],
+    cache: &mut ParseCache<'a>,
+    scribe: &mut Scribe,
 ) -> Dts<'a> {
     let mut span = Span::new("", 0, 0).unwrap();
     let mut top_def = Vec::new_in(arena);
     for path in paths {
         match loader.read_utf8(path.into()) {
-            Ok(Some((_, src))) => match parse_typed(src, arena) {
+            Ok(Some((_, src))) => match cache.parse(src, arena) {
                 Ok(dts) => {
                     if span.as_str().is_empty() {
                         span = dts._span;
                     }
-                    visit_includes(1, loader, arena, path, dts, &mut top_def, scribe);
+                    visit_includes(1, loader, arena, path, dts, &mut top_def, cache, scribe);
                 }
                 // TODO:  is with_path() needed here?
                 Err(e) => scribe.err(e.with_path(path)),
@@ -89,6 +134,7 @@ pub fn parse_concat_with_includes<'a>(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn visit_includes<'a>(
     depth: usize,
     loader: &'a impl Loader,
@@ -96,6 +142,7 @@ fn visit_includes<'a>(
     path: &Path,
     dts: &Dts<'a>,
     out: &mut Vec<&'a rules::TopDef<'a>>,
+    cache: &mut ParseCache<'a>,
     scribe: &mut Scribe,
 ) {
     let dir = path.parent().unwrap();
@@ -113,8 +160,8 @@ fn visit_includes<'a>(
         let pathspan = include.quoted_string.trim_one();
         // The path is not unescaped in any way before use.
         match loader.find_utf8(dir, Path::new(pathspan.as_str())) {
-            Ok(Some((ipath, src))) => match parse_typed(src, arena) {
-                Ok(dts) => visit_includes(depth + 1, loader, arena, ipath, dts, out, scribe),
+            Ok(Some((ipath, src))) => match cache.parse(src, arena) {
+                Ok(dts) => visit_includes(depth + 1, loader, arena, ipath, dts, out, cache, scribe),
                 Err(e) => scribe.err(e.with_path(ipath)),
             },
             // TODO:  distinguish UTF-8 errors here (Err(...) vs Ok(None))
@@ -1,6 +1,10 @@
-use crate::parse::{Parsed, Rule};
+use crate::BinaryNode;
+use crate::error::{Edit, SourceError};
+use crate::fs::Loader;
+use crate::parse::{Parsed, Rule, parse_untyped};
 use core::fmt::Write;
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 pub fn format(dts: Parsed) -> String {
     let mut pretty = PrettyPrinter::new();
@@ -8,6 +12,76 @@ pub fn format(dts: Parsed) -> String {
     pretty.out.buffer
 }
 
+/// Pretty-print a tree of evaluated values as source.  Types are lost, so each value is shown
+/// in whatever form its bytes suggest.
+pub fn format_binary(tree: &BinaryNode) -> String {
+    let source = format!("/dts-v1/;/{tree};");
+    // Reparse and pretty-print the output.
+    format(parse_untyped(&source).unwrap())
+}
+
+/// Apply `edits` to `input`, leaving the rest of its text (and so its formatting) untouched.
+/// Duplicate edits are applied once; of overlapping edits, only the first is applied, and the
+/// number skipped is returned with the text.  Fails if the result does not parse.
+pub fn apply_edits(input: &str, edits: &[&Edit]) -> Result<(String, usize), SourceError> {
+    let mut edits = edits.to_vec();
+    edits.sort_by_key(|e| (e.range.start, e.range.end));
+    edits.dedup();
+    let mut output = String::new();
+    let mut copied = 0;
+    let mut skipped = 0;
+    for edit in edits {
+        if edit.range.start < copied {
+            skipped += 1;
+            continue;
+        }
+        output.push_str(&input[copied..edit.range.start]);
+        output.push_str(&edit.replacement);
+        copied = edit.range.end;
+    }
+    output.push_str(&input[copied..]);
+    parse_untyped(&output)?;
+    Ok((output, skipped))
+}
+
+/// The new contents of a file changed by `apply_fixes()`.
+pub struct FixedFile {
+    pub path: PathBuf,
+    pub text: String,
+    /// The number of edits not applied because they overlapped another.
+    pub skipped: usize,
+}
+
+/// Apply `edits` to the files loaded by `loader`, returning the new contents of each file which
+/// changed.
+pub fn apply_fixes(loader: &impl Loader, edits: &[Edit]) -> Result<Vec<FixedFile>, String> {
+    let mut by_file: Vec<(PathBuf, Vec<&Edit>)> = vec![];
+    for edit in edits {
+        let Some(path) = loader.path_of_buffer(edit.buffer()) else {
+            return Err("cannot attribute a fix to a file".into());
+        };
+        match by_file.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, file_edits)) => file_edits.push(edit),
+            None => by_file.push((path, vec![edit])),
+        }
+    }
+    let mut files = vec![];
+    for (path, edits) in by_file {
+        let input = match loader.read_utf8(path.clone()) {
+            Ok(Some((_, input))) => input,
+            _ => return Err(format!("cannot reread {path:?}")),
+        };
+        let (text, skipped) =
+            apply_edits(input, &edits).map_err(|e| format!("fixing {path:?}:\n{e}"))?;
+        files.push(FixedFile {
+            path,
+            text,
+            skipped,
+        });
+    }
+    Ok(files)
+}
+
 #[derive(Default)]
 struct IndentingWriter {
     buffer: String,
@@ -0,0 +1,451 @@
+//! Facilities for explaining where the nodes and properties of a merged tree came from.
+//!
+//! `merge::merge()` records every definition and deletion of each node and property.  This
+//! module interprets those records, so that one can ask which source lines produced a value.
+
+use crate::fs::Loader;
+use crate::line::LineTableCache;
+use crate::merge::{NodeChange, NodeChanges, PropChange, PropChanges};
+use crate::node::{Node, OptionDisplay};
+use crate::path::NodePath;
+use core::fmt::{Display, Formatter};
+use pest::Span;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    /// The node or property was defined where it did not exist.
+    Created,
+    /// An existing node was reopened, or an existing property was overridden.
+    Updated,
+    /// The node or property was deleted, either directly or along with an ancestor.
+    Deleted,
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Action::Created => "created",
+            Action::Updated => "updated",
+            Action::Deleted => "deleted",
+        })
+    }
+}
+
+/// One step in the history of a node or property.
+#[derive(Clone, Debug)]
+pub struct Event<'a> {
+    pub action: Action,
+    pub span: Span<'a>,
+    /// Whether this step is reflected in the merged tree.  For a property, this is true only of
+    /// the final definition, if the property survives.  For a node, it is true of each definition
+    /// since the node was last created.
+    pub effective: bool,
+}
+
+/// Interpret the changes recorded for a node.
+pub fn node_history<'a>(changes: &[NodeChange<'a>]) -> Vec<Event<'a>> {
+    let deleted = |c: &NodeChange| matches!(c, NodeChange::TopDelNode(_) | NodeChange::DelNode(_));
+    let spans = changes.iter().map(|c| (deleted(c), *c.span()));
+    let mut events = history(spans);
+    // Every definition since the last creation contributes to the node.
+    if let Some(last) = events.iter().rposition(|e| e.action == Action::Created)
+        && events[last..].iter().all(|e| e.action != Action::Deleted)
+    {
+        for e in &mut events[last..] {
+            e.effective = true;
+        }
+    }
+    events
+}
+
+/// Interpret the changes recorded for a property.
+pub fn property_history<'a>(changes: &[PropChange<'a>]) -> Vec<Event<'a>> {
+    let deleted = |c: &PropChange| !matches!(c, PropChange::Prop(_));
+    let spans = changes.iter().map(|c| (deleted(c), *c.span()));
+    let mut events = history(spans);
+    // Only the final definition contributes to the property's value.
+    if let Some(last) = events.last_mut()
+        && last.action != Action::Deleted
+    {
+        last.effective = true;
+    }
+    events
+}
+
+fn history<'a>(changes: impl Iterator<Item = (bool, Span<'a>)>) -> Vec<Event<'a>> {
+    let mut exists = false;
+    let mut events = vec![];
+    for (deleted, span) in changes {
+        let action = match (deleted, exists) {
+            (true, _) => Action::Deleted,
+            (false, true) => Action::Updated,
+            (false, false) => Action::Created,
+        };
+        exists = !deleted;
+        events.push(Event {
+            action,
+            span,
+            effective: false,
+        });
+    }
+    events
+}
+
+/// The histories of a node and/or a property found at one path.
+pub struct Provenance<'a> {
+    pub path: NodePath,
+    pub node: Option<Vec<Event<'a>>>,
+    pub property: Option<Vec<Event<'a>>>,
+}
+
+/// Look up the history of the node or property named by `path`.  Since a node and a property
+/// may share a name, both are returned if present.  Returns `None` if neither was ever defined.
+pub fn lookup<'a>(
+    node_changes: &NodeChanges<'a>,
+    prop_changes: &PropChanges<'a>,
+    path: &NodePath,
+) -> Option<Provenance<'a>> {
+    let node = node_changes.get(path).map(|c| node_history(c));
+    let property = prop_changes.get(path).map(|c| property_history(c));
+    if node.is_none() && property.is_none() {
+        return None;
+    }
+    let path = path.clone();
+    Some(Provenance {
+        path,
+        node,
+        property,
+    })
+}
+
+/// Report the file, line, and column (in codepoints) where `span` starts.
+pub fn source_location<'a>(
+    loader: &impl Loader,
+    ltc: &LineTableCache<'a>,
+    span: &Span<'a>,
+) -> (PathBuf, usize, usize) {
+    let buffer = span.get_input().as_bytes().as_ptr_range();
+    let path = loader.path_of_buffer(buffer).unwrap_or("<unknown>".into());
+    let (line, col) = ltc.start_line_col(span);
+    (path, line, col)
+}
+
+impl Provenance<'_> {
+    /// Describe each step of the history, one per line, marking the steps which are reflected in
+    /// the merged tree.
+    pub fn describe(&self, loader: &impl Loader) -> String {
+        use core::fmt::Write;
+        let ltc = LineTableCache::default();
+        let mut out = String::new();
+        for (kind, events) in [("node", &self.node), ("property", &self.property)] {
+            let Some(events) = events else {
+                continue;
+            };
+            _ = writeln!(out, "history of {kind} {}:", self.path);
+            for event in events {
+                let (file, line, col) = source_location(loader, &ltc, &event.span);
+                let file = file.display();
+                let mark = if event.effective { " (effective)" } else { "" };
+                _ = writeln!(out, "  {} at {file}:{line}:{col}{mark}", event.action);
+            }
+            if events.last().is_some_and(|e| e.action == Action::Deleted) {
+                _ = writeln!(out, "  (absent from the merged tree)");
+            }
+        }
+        out
+    }
+}
+
+/// A definition of a node or property which has no effect on the merged tree.
+pub struct Shadowed<'a> {
+    pub path: NodePath,
+    /// "node" or "property".
+    pub kind: &'static str,
+    /// The definition.
+    pub span: Span<'a>,
+    /// The later definition overriding the property, or the deletion of the node or property.
+    pub cause: Event<'a>,
+}
+
+/// Find every definition which has no effect on the merged tree, because a property is
+/// defined again or deleted, or a node is deleted.  Definitions within a node definition which
+/// has no effect are omitted, as that definition accounts for them.
+pub fn shadowed<'a>(
+    node_changes: &NodeChanges<'a>,
+    prop_changes: &PropChanges<'a>,
+) -> Vec<Shadowed<'a>> {
+    let mut found = vec![];
+    let histories = node_changes
+        .iter()
+        .map(|(path, c)| (path, "node", node_history(c)))
+        .chain(
+            prop_changes
+                .iter()
+                .map(|(path, c)| (path, "property", property_history(c))),
+        );
+    for (path, kind, events) in histories {
+        for (i, event) in events.iter().enumerate() {
+            if event.action == Action::Deleted || event.effective {
+                continue;
+            }
+            // Only a deletion can undo a node definition; any later event supersedes a property.
+            let cause = events[i + 1..]
+                .iter()
+                .find(|e| kind == "property" || e.action == Action::Deleted);
+            found.push(Shadowed {
+                path: path.clone(),
+                kind,
+                span: event.span,
+                cause: cause.unwrap().clone(),
+            });
+        }
+    }
+    let contains = |outer: &Span, inner: &Span| {
+        core::ptr::eq(outer.get_input(), inner.get_input())
+            && outer.start() <= inner.start()
+            && inner.end() <= outer.end()
+            && outer != inner
+    };
+    let nodes: Vec<Span> = found
+        .iter()
+        .filter(|s| s.kind == "node")
+        .map(|s| s.span)
+        .collect();
+    found.retain(|s| !nodes.iter().any(|node| contains(node, &s.span)));
+    found
+}
+
+/// List shadowed definitions, grouped by file and ordered by position within each file.
+pub fn describe_shadowed(shadowed: &[Shadowed], loader: &impl Loader) -> String {
+    use core::fmt::Write;
+    let ltc = LineTableCache::default();
+    let mut by_file = std::collections::BTreeMap::<PathBuf, Vec<_>>::new();
+    for s in shadowed {
+        let (file, line, col) = source_location(loader, &ltc, &s.span);
+        by_file.entry(file).or_default().push((line, col, s));
+    }
+    let mut out = String::new();
+    for (file, mut entries) in by_file {
+        entries.sort_by_key(|&(line, col, _)| (line, col));
+        _ = writeln!(out, "{}:", file.display());
+        for (line, col, s) in entries {
+            let (cause_file, cause_line, cause_col) = source_location(loader, &ltc, &s.cause.span);
+            let cause = match s.cause.action {
+                Action::Deleted => "deleted",
+                _ => "overridden",
+            };
+            _ = writeln!(
+                out,
+                "  {line}:{col}: {} {} {cause} at {}:{cause_line}:{cause_col}",
+                s.kind,
+                s.path,
+                cause_file.display()
+            );
+        }
+    }
+    out
+}
+
+/// Format a tree like `Node`'s `Display` implementation, adding a comment to each node and
+/// property with the locations of the definitions which are reflected in the tree (per
+/// `Event::effective`).  Nodes and properties with no recorded history, such as phandles
+/// assigned during evaluation, are not annotated.
+pub fn annotate<'a, P: OptionDisplay>(
+    node: &Node<P>,
+    node_changes: &NodeChanges<'a>,
+    prop_changes: &PropChanges<'a>,
+    loader: &impl Loader,
+) -> String {
+    let ltc = LineTableCache::default();
+    let mut out = String::new();
+    let annotator = Annotator {
+        node_changes,
+        prop_changes,
+        loader,
+        ltc: &ltc,
+    };
+    annotator.write_node(&mut out, node, &NodePath::root());
+    out
+}
+
+struct Annotator<'t, 'a, L> {
+    node_changes: &'t NodeChanges<'a>,
+    prop_changes: &'t PropChanges<'a>,
+    loader: &'t L,
+    ltc: &'t LineTableCache<'a>,
+}
+
+impl<'a, L: Loader> Annotator<'_, 'a, L> {
+    fn comment(&self, events: Option<Vec<Event<'a>>>) -> String {
+        let locations: Vec<String> = events
+            .unwrap_or_default()
+            .iter()
+            .filter(|e| e.effective)
+            .map(|e| {
+                let (file, line, col) = source_location(self.loader, self.ltc, &e.span);
+                format!("{}:{line}:{col}", file.display())
+            })
+            .collect();
+        if locations.is_empty() {
+            return String::new();
+        }
+        format!(" /* {} */", locations.join(", ").replace("*/", "* /"))
+    }
+
+    fn write_node<P: OptionDisplay>(&self, out: &mut String, node: &Node<P>, path: &NodePath) {
+        use core::fmt::Write;
+        let history = self.node_changes.get(path).map(|c| node_history(c));
+        _ = writeln!(out, "{{{}", self.comment(history));
+        for (name, value) in node.properties() {
+            let history = self.prop_changes.get(&path.join(name));
+            let comment = self.comment(history.map(|c| property_history(c)));
+            match value.fmt_opt() {
+                Some(value) => _ = writeln!(out, "{name} = {value};{comment}"),
+                None => _ = writeln!(out, "{name};{comment}"),
+            }
+        }
+        if node.properties().next().is_some() && node.children().next().is_some() {
+            _ = writeln!(out);
+        }
+        for (name, child) in node.children() {
+            _ = write!(out, "{}{name} ", child.labels_as_display());
+            self.write_node(out, child, &path.join(name));
+            _ = writeln!(out, ";");
+        }
+        _ = write!(out, "}}");
+    }
+}
+
+#[test]
+fn test_history() {
+    let source = r#"
+/ {
+    a { x = <1>; y = <1>; };
+};
+/ {
+    a { x = <2>; /delete-property/ y; };
+};
+/ {
+    /delete-node/ a;
+    a { x = <3>; };
+    b { z; };
+};
+/ {
+    b { z = <4>; };
+};
+"#;
+    let arena = crate::Arena::new();
+    let dts = crate::parse::parse_typed(source, &arena).unwrap();
+    let mut scribe = crate::error::Scribe::new(true);
+    let (_, _, node_changes, prop_changes) = crate::merge::merge(dts, &mut scribe);
+    assert!(scribe.report(&crate::fs::DummyLoader, &mut std::io::stderr()));
+    fn summary<'a>(events: &[Event<'a>]) -> Vec<(Action, bool, &'a str)> {
+        events
+            .iter()
+            .map(|e| (e.action, e.effective, e.span.as_str()))
+            .collect()
+    }
+    let path = |s: &str| {
+        let mut p = NodePath::root();
+        p.push(s);
+        p
+    };
+
+    let x = lookup(&node_changes, &prop_changes, &path("a/x")).unwrap();
+    assert!(x.node.is_none());
+    use Action::*;
+    assert_eq!(
+        summary(&x.property.unwrap()),
+        [
+            (Created, false, "x = <1>;"),
+            (Updated, false, "x = <2>;"),
+            (Deleted, false, "/delete-node/ a;"),
+            (Created, true, "x = <3>;"),
+        ]
+    );
+
+    let y = lookup(&node_changes, &prop_changes, &path("a/y")).unwrap();
+    assert_eq!(
+        summary(&y.property.unwrap()),
+        [
+            (Created, false, "y = <1>;"),
+            (Deleted, false, "/delete-property/ y;"),
+        ]
+    );
+
+    let a = lookup(&node_changes, &prop_changes, &path("a")).unwrap();
+    let a: Vec<_> = a
+        .node
+        .unwrap()
+        .iter()
+        .map(|e| (e.action, e.effective))
+        .collect();
+    assert_eq!(
+        a,
+        [
+            (Created, false),
+            (Updated, false),
+            (Deleted, false),
+            (Created, true),
+        ]
+    );
+
+    let z = lookup(&node_changes, &prop_changes, &path("b/z")).unwrap();
+    let z = summary(&z.property.unwrap());
+    assert_eq!(z, [(Created, false, "z;"), (Updated, true, "z = <4>;")]);
+
+    assert!(lookup(&node_changes, &prop_changes, &path("c")).is_none());
+}
+
+#[test]
+fn test_annotate() {
+    let source = "/ { a = <1>; n { b; }; };\n/ { a = <2>; };\n";
+    let arena = crate::Arena::new();
+    let dts = crate::parse::parse_typed(source, &arena).unwrap();
+    let mut scribe = crate::error::Scribe::new(true);
+    let (tree, _, node_changes, prop_changes) = crate::merge::merge(dts, &mut scribe);
+    assert!(scribe.report(&crate::fs::DummyLoader, &mut std::io::stderr()));
+    let loader = crate::fs::DummyLoader;
+    let annotated = annotate(&tree, &node_changes, &prop_changes, &loader);
+    assert_eq!(
+        annotated,
+        "{ /* <unknown>:1:1, <unknown>:2:1 */\n\
+         a = <2>; /* <unknown>:2:5 */\n\
+         \n\
+         n { /* <unknown>:1:14 */\n\
+         b; /* <unknown>:1:18 */\n\
+         };\n\
+         }"
+    );
+}
+
+#[test]
+fn test_shadowed() {
+    let source = r#"
+/ {
+    a { x = <1>; y; };
+    b { z = <1>; };
+};
+/ {
+    a { x = <2>; };
+    /delete-node/ b;
+};
+/ {
+    a { /delete-property/ y; };
+};
+"#;
+    let arena = crate::Arena::new();
+    let dts = crate::parse::parse_typed(source, &arena).unwrap();
+    let mut scribe = crate::error::Scribe::new(true);
+    let (_, _, node_changes, prop_changes) = crate::merge::merge(dts, &mut scribe);
+    assert!(scribe.report(&crate::fs::DummyLoader, &mut std::io::stderr()));
+    let shadowed = shadowed(&node_changes, &prop_changes);
+    assert_eq!(
+        describe_shadowed(&shadowed, &crate::fs::DummyLoader),
+        "<unknown>:\n\
+         \x20 3:9: property /a/x overridden at <unknown>:7:9\n\
+         \x20 3:18: property /a/y deleted at <unknown>:11:9\n\
+         \x20 4:5: node /b deleted at <unknown>:8:5\n"
+    );
+}
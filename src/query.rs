@@ -0,0 +1,508 @@
+//! Selecting nodes with a small query language, as in
+//! `/soc/**[compatible*="arm,pl011"][status!="disabled"]`.
+//!
+//! A selector is an optional path pattern followed by any number of filters, all of which a node
+//! must satisfy:
+//!
+//! - A pattern beginning with '/' matches whole paths, and otherwise matches the last segments of
+//!   a path.  Within a segment, '*' matches any run of characters; a segment of `**` matches any
+//!   number of segments, including none.  An empty pattern matches every node.
+//! - `[name="value"]` matches nodes whose property `name` is a string list including `value`.
+//!   `^=`, `$=` and `*=` match strings starting with, ending with, or containing `value`, and
+//!   `!=` matches nodes which `=` does not, including those lacking the property.
+//! - `:has-prop(name)` matches nodes having the property `name`.
+//! - `:referenced-by(selector)` matches nodes referenced by a node matching `selector`, by phandle
+//!   or path.
+
+use crate::checks::{Context, PHANDLE_ARGS};
+use crate::eval::UnescapeExt;
+use crate::label::{LabelMap, LabelResolver};
+use crate::node::Node;
+use crate::parse::TypedRuleExt;
+use crate::parse::rules::{Cell, LabelOrCell, Prop, Value};
+use crate::path::NodePath;
+use crate::{BinaryNode, SourceNode};
+use std::collections::{HashMap, HashSet};
+
+/// A parsed selector.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Selector {
+    pattern: Vec<Segment>,
+    filters: Vec<Filter>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    /// A node name, possibly containing '*' wildcards.
+    Name(String),
+    /// `**`, matching any number of segments.
+    Any,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Filter {
+    Property(String, Operator, String),
+    HasProp(String),
+    ReferencedBy(Selector),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Prefix,
+    Suffix,
+    Contains,
+}
+
+impl Selector {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut parser = Parser { input, pos: 0 };
+        let selector = parser.selector()?;
+        match parser.rest() {
+            "" => Ok(selector),
+            rest => Err(format!("unexpected {rest:?} in selector {input:?}")),
+        }
+    }
+
+    fn matches_path(&self, path: &NodePath) -> bool {
+        let segments: Vec<&str> = path.segments().collect();
+        matches_segments(&self.pattern, &segments)
+    }
+}
+
+fn matches_segments(pattern: &[Segment], segments: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => segments.is_empty(),
+        Some((Segment::Any, rest)) => {
+            (0..=segments.len()).any(|skip| matches_segments(rest, &segments[skip..]))
+        }
+        Some((Segment::Name(name), rest)) => match segments.split_first() {
+            Some((segment, tail)) => matches_glob(name, segment) && matches_segments(rest, tail),
+            None => false,
+        },
+    }
+}
+
+/// Whether `text` matches `glob`, in which '*' matches any run of characters.
+fn matches_glob(glob: &str, text: &str) -> bool {
+    let mut parts = glob.split('*');
+    let first = parts.next().unwrap();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // There was no '*'.
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+struct Parser<'i> {
+    input: &'i str,
+    pos: usize,
+}
+
+impl<'i> Parser<'i> {
+    fn rest(&self) -> &'i str {
+        &self.input[self.pos..]
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(format!(
+                "expected {token:?} at {:?} in selector {:?}",
+                self.rest(),
+                self.input
+            ))
+        }
+    }
+
+    /// Consume characters up to the first of `stop`.
+    fn take_until(&mut self, stop: &[char]) -> &'i str {
+        let rest = self.rest();
+        let len = rest.find(stop).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn selector(&mut self) -> Result<Selector, String> {
+        let pattern = self.take_until(&['[', ':', ')']);
+        let mut segments: Vec<Segment> = pattern
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| match s {
+                "**" => Segment::Any,
+                _ => Segment::Name(s.into()),
+            })
+            .collect();
+        if pattern.is_empty() {
+            segments.push(Segment::Any);
+        } else if !pattern.starts_with('/') {
+            segments.insert(0, Segment::Any);
+        }
+        let mut filters = vec![];
+        loop {
+            if self.eat("[") {
+                let name = self.take_until(&['=', '!', '^', '$', '*', ']']);
+                let operator = [
+                    ("=", Operator::Equal),
+                    ("!=", Operator::NotEqual),
+                    ("^=", Operator::Prefix),
+                    ("$=", Operator::Suffix),
+                    ("*=", Operator::Contains),
+                ]
+                .into_iter()
+                .find(|(token, _)| self.eat(token));
+                let Some((_, operator)) = operator else {
+                    return Err(format!(
+                        "expected an operator after [{name} in selector {:?}",
+                        self.input
+                    ));
+                };
+                let value = self.quoted_string()?;
+                self.expect("]")?;
+                filters.push(Filter::Property(name.into(), operator, value));
+            } else if self.eat(":has-prop(") {
+                let name = self.take_until(&[')']);
+                self.expect(")")?;
+                filters.push(Filter::HasProp(name.into()));
+            } else if self.eat(":referenced-by(") {
+                let selector = self.selector()?;
+                self.expect(")")?;
+                filters.push(Filter::ReferencedBy(selector));
+            } else if self.rest().starts_with(':') {
+                let name = self.take_until(&['(']);
+                return Err(format!(
+                    "unknown filter {name} in selector {:?}",
+                    self.input
+                ));
+            } else {
+                return Ok(Selector {
+                    pattern: segments,
+                    filters,
+                });
+            }
+        }
+    }
+
+    /// A string in double quotes, in which '\' escapes the next character.
+    fn quoted_string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut value = String::new();
+        let mut chars = self.rest().chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos = self.input.len() - chars.as_str().len();
+                    return Ok(value);
+                }
+                '\\' => value.extend(chars.next()),
+                c => value.push(c),
+            }
+        }
+        Err(format!("unterminated string in selector {:?}", self.input))
+    }
+}
+
+/// Property values which selectors can compare with strings.
+pub trait QueryValue {
+    /// The value as a list of strings, or `None` if it is not one.
+    fn strings(&self) -> Option<Vec<String>>;
+}
+
+impl QueryValue for Vec<u8> {
+    fn strings(&self) -> Option<Vec<String>> {
+        let s = core::str::from_utf8(self.strip_suffix(&[0])?).ok()?;
+        Some(s.split('\0').map(String::from).collect())
+    }
+}
+
+impl QueryValue for &Prop<'_> {
+    fn strings(&self) -> Option<Vec<String>> {
+        let propvalue = self.prop_value?;
+        let strings = propvalue.labeled_value.iter().map(|lv| match lv.value {
+            Value::QuotedString(s) => String::from_utf8(s.unescape().ok()?.into_owned()).ok(),
+            _ => None,
+        });
+        strings.collect()
+    }
+}
+
+/// A tree to select nodes from, with the references between its nodes.
+pub struct Query<'a, P> {
+    tree: &'a Node<P>,
+    /// The nodes referenced by each node.
+    references: HashMap<NodePath, Vec<NodePath>>,
+}
+
+/// The node named by `value` if it is a string holding an absolute path, as in `/aliases`.
+/// Anything after a ':' is ignored, as in `stdout-path = "/serial@1000:115200n8";`.
+fn path_reference(tree: &BinaryNode, value: &Vec<u8>) -> Option<NodePath> {
+    let [string] = &value.strings()?[..] else {
+        return None;
+    };
+    let path = string.split(':').next().unwrap();
+    if !path.starts_with('/') {
+        return None;
+    }
+    let path = NodePath::root().join(path);
+    tree.walk(path.segments()).map(|_| path)
+}
+
+/// Properties holding lists of phandles without arguments, besides `pinctrl-N`.
+const PHANDLE_LISTS: &[&str] = &[
+    "cpu",
+    "interrupt-parent",
+    "memory-region",
+    "remote-endpoint",
+];
+
+impl<'a> Query<'a, Vec<u8>> {
+    /// Query a compiled tree, where references are phandles in the properties known to hold them.
+    pub fn binary(tree: &'a BinaryNode) -> Self {
+        let ctx = Context::new(tree, None);
+        let interrupts = ctx.interrupt_resolver();
+        let mut references = HashMap::new();
+        tree.for_each_node(&NodePath::root(), &mut |path, node| {
+            let mut targets = vec![];
+            for (name, value) in node.properties() {
+                if let Some(target) = path_reference(tree, value) {
+                    targets.push(target);
+                }
+                let Some(cells) = node.get_cells(name) else {
+                    continue;
+                };
+                if let Some(p) = PHANDLE_ARGS.iter().find(|p| p.matches(name)) {
+                    let specifiers = ctx.phandle_args(&cells, p.cells, p.default_cells);
+                    let specifiers = specifiers.unwrap_or_default();
+                    targets.extend(specifiers.into_iter().map(|s| s.provider));
+                } else if name == "interrupt-map" {
+                    let entries = interrupts.map_entries(path).unwrap_or_default();
+                    targets.extend(entries.into_iter().map(|e| e.parent));
+                } else if PHANDLE_LISTS.contains(&name.as_str()) || is_pinctrl_state(name) {
+                    let found = cells.iter().filter_map(|&p| ctx.phandle_target(p));
+                    targets.extend(found.map(|(target, _)| target.clone()));
+                }
+            }
+            references.insert(path.clone(), targets);
+        });
+        Self { tree, references }
+    }
+}
+
+fn is_pinctrl_state(name: &str) -> bool {
+    name.strip_prefix("pinctrl-")
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+impl<'a, 'i> Query<'a, &'i Prop<'i>> {
+    /// Query a merged source tree, where references are written `&label` or `&{/path}`.
+    pub fn source(tree: &'a SourceNode<'i>) -> Self {
+        let mut labels = LabelMap::new();
+        tree.for_each_node(&NodePath::root(), &mut |path, node| {
+            for label in node.labels() {
+                labels.insert(label.clone(), path.clone());
+            }
+        });
+        let resolver = LabelResolver(&labels, tree);
+        let mut references = HashMap::new();
+        tree.for_each_node(&NodePath::root(), &mut |path, node| {
+            let mut targets = vec![];
+            let values = node.properties().flat_map(|(_, p)| p.prop_value);
+            for labeled_value in values.flat_map(|pv| pv.labeled_value) {
+                let noderefs: Vec<_> = match &labeled_value.value {
+                    Value::NodeReference(noderef) => vec![noderef],
+                    Value::Cells(cells) => cells
+                        .label_or_cell
+                        .iter()
+                        .filter_map(|c| match c {
+                            LabelOrCell::Cell(Cell::NodeReference(noderef)) => Some(noderef),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => vec![],
+                };
+                let found = noderefs
+                    .into_iter()
+                    .filter_map(|r| resolver.resolve_str(path, r.str()));
+                targets.extend(found);
+            }
+            references.insert(path.clone(), targets);
+        });
+        Self { tree, references }
+    }
+}
+
+impl<'a, P: QueryValue> Query<'a, P> {
+    /// The nodes matching `selector`, parents before children.
+    pub fn select(&self, selector: &Selector) -> Vec<(NodePath, &'a Node<P>)> {
+        // The targets of each :referenced-by() filter.
+        let referenced: Vec<Option<HashSet<NodePath>>> = selector
+            .filters
+            .iter()
+            .map(|filter| match filter {
+                Filter::ReferencedBy(referrers) => Some(
+                    self.select(referrers)
+                        .into_iter()
+                        .flat_map(|(path, _)| self.references.get(&path).into_iter().flatten())
+                        .cloned()
+                        .collect(),
+                ),
+                _ => None,
+            })
+            .collect();
+        let matches = |path: &NodePath, node: &Node<P>| {
+            let filters = selector.filters.iter().zip(&referenced);
+            selector.matches_path(path)
+                && filters
+                    .into_iter()
+                    .all(|(filter, referenced)| match filter {
+                        Filter::Property(name, operator, value) => {
+                            let strings = node.get_property(name).and_then(P::strings);
+                            matches_strings(strings.as_deref(), *operator, value)
+                        }
+                        Filter::HasProp(name) => node.get_property(name).is_some(),
+                        Filter::ReferencedBy(_) => referenced.as_ref().unwrap().contains(path),
+                    })
+        };
+        let mut selected = vec![];
+        self.tree
+            .for_each_node(&NodePath::root(), &mut |path, node| {
+                if matches(path, node) {
+                    selected.push((path.clone(), node));
+                }
+            });
+        selected
+    }
+}
+
+fn matches_strings(strings: Option<&[String]>, operator: Operator, value: &str) -> bool {
+    let any = |f: &dyn Fn(&str) -> bool| strings.is_some_and(|s| s.iter().any(|s| f(s)));
+    match operator {
+        Operator::Equal => any(&|s| s == value),
+        Operator::NotEqual => !any(&|s| s == value),
+        Operator::Prefix => any(&|s| s.starts_with(value)),
+        Operator::Suffix => any(&|s| s.ends_with(value)),
+        Operator::Contains => any(&|s| s.contains(value)),
+    }
+}
+
+/// A copy of `tree` holding only the nodes at `paths`, with their labels and properties, and the
+/// ancestors needed to reach them.  Ancestors keep `#address-cells` and `#size-cells`, so that
+/// the `reg` of each node still reads as before.
+pub fn extract<P: Clone>(tree: &Node<P>, paths: &[NodePath]) -> Node<P> {
+    let mut result = Node::default();
+    for path in paths {
+        let Some(node) = tree.walk(path.segments()) else {
+            continue;
+        };
+        let mut copy = &mut result;
+        let mut original = tree;
+        for segment in path.segments() {
+            for name in ["#address-cells", "#size-cells"] {
+                if let Some(value) = original.get_property(name) {
+                    copy.set_property(name, value.clone());
+                }
+            }
+            copy = copy.add_child(segment);
+            original = original.get_child(segment).unwrap();
+        }
+        for label in node.labels() {
+            copy.add_label(label);
+        }
+        for (name, value) in node.properties() {
+            copy.set_property(name, value.clone());
+        }
+    }
+    result
+}
+
+#[test]
+fn test_query() {
+    let source = r#"
+/dts-v1/;
+/ {
+    soc {
+        #address-cells = <1>;
+        #size-cells = <0>;
+        uart0: serial@1000 { compatible = "arm,pl011", "arm,primecell"; dmas = <&dma 1>; };
+        serial@2000 { compatible = "arm,pl011", "arm,primecell"; status = "disabled"; };
+        serial@3000 { compatible = "ns16550a"; };
+        dma: dma@4000 { compatible = "arm,pl330"; #dma-cells = <1>; };
+    };
+    chosen { stdout-path = &uart0; };
+};
+"#;
+    let arena = crate::Arena::new();
+    let dts = crate::parse::parse_typed(source, &arena).unwrap();
+    let mut scribe = crate::error::Scribe::new(true);
+    let (tree, labels, _, _) = crate::merge::merge(dts, &mut scribe);
+    let binary = crate::eval::eval(tree.clone(), labels, &crate::fs::DummyLoader, &mut scribe);
+    assert!(scribe.report(&crate::fs::DummyLoader, &mut std::io::stderr()));
+    let (source, binary) = (Query::source(&tree), Query::binary(&binary));
+    let check = |selector: &str, expected: &[&str]| {
+        let selector = Selector::parse(selector).unwrap();
+        let paths = source
+            .select(&selector)
+            .into_iter()
+            .map(|(p, _)| p.display());
+        assert_eq!(paths.collect::<Vec<_>>(), expected, "source {selector:?}");
+        let paths = binary
+            .select(&selector)
+            .into_iter()
+            .map(|(p, _)| p.display());
+        assert_eq!(paths.collect::<Vec<_>>(), expected, "binary {selector:?}");
+    };
+    check(
+        r#"/soc/**[compatible*="pl011"][status!="disabled"]"#,
+        &["/soc/serial@1000"],
+    );
+    check(
+        "serial@*",
+        &["/soc/serial@1000", "/soc/serial@2000", "/soc/serial@3000"],
+    );
+    check("/*", &["/soc", "/chosen"]);
+    check(
+        r#"[compatible^="arm,"]:has-prop(status)"#,
+        &["/soc/serial@2000"],
+    );
+    check(
+        r#"[compatible="arm,primecell"]"#,
+        &["/soc/serial@1000", "/soc/serial@2000"],
+    );
+    check(r#"[compatible$="550a"]"#, &["/soc/serial@3000"]);
+    check(":referenced-by(/chosen)", &["/soc/serial@1000"]);
+    check(":referenced-by(:has-prop(dmas))", &["/soc/dma@4000"]);
+    check(":referenced-by(/nothing)", &[]);
+    assert_eq!(
+        Selector::parse(r#"[compatible="x"#),
+        Err(r#"unterminated string in selector "[compatible=\"x""#.into())
+    );
+    assert_eq!(
+        Selector::parse(":has(x)"),
+        Err(r#"unknown filter :has in selector ":has(x)""#.into())
+    );
+    let extracted = extract(&tree, &[NodePath::root().join("soc/dma@4000")]);
+    assert_eq!(
+        format!("{extracted}"),
+        "{\nsoc {\n#address-cells = <1>;\n#size-cells = <0>;\n\ndma: dma@4000 {\ncompatible = \"arm,pl330\";\n#dma-cells = <1>;\n};\n};\n}"
+    );
+}
@@ -0,0 +1,788 @@
+//! Validation of nodes against devicetree bindings written in the YAML format of dt-schema,
+//! without Python or network access.
+//!
+//! Only a subset of JSON Schema is understood:  `properties`, `patternProperties`, `required`,
+//! `type`, `enum`, `const`, `minItems`, `maxItems`, `allOf`, and `$ref`.  Other keywords are
+//! ignored.  A property's schema may also be `false`, forbidding it.
+//!
+//! Of `type`, `boolean` means an empty property, `string` a single string, `integer` a single
+//! 32-bit integer, `array` any nonempty value, and `object` a node.  Other types are reported as
+//! unsupported.
+//!
+//! A binding applies to each node whose `compatible` property contains a string mentioned by the
+//! binding's own `compatible` schema (in a `const` or `enum`, at any depth), unless the binding
+//! says `select: false`.
+//!
+//! A `$ref` names another file in the directory as `/schemas/<path>`,
+//! `http://devicetree.org/schemas/<path>`, or a path relative to the referring file, optionally
+//! followed by a JSON pointer such as `#/definitions/port`.  The property types of dt-schema's
+//! own `types.yaml`, as in `$ref: /schemas/types.yaml#/definitions/uint32`, are understood
+//! without that file.  Other references which cannot be resolved match anything, as do patterns
+//! which the `regex` crate does not accept.
+//!
+//! As in dt-schema, `minItems` and `maxItems` count entries of `reg` (sized by the parent's
+//! `#address-cells` and `#size-cells`), of `interrupts` (sized by the interrupt parent's
+//! `#interrupt-cells`), and of the phandle lists of `checks::PHANDLE_ARGS` (sized by each
+//! provider), rather than cells.
+
+use crate::BinaryNode;
+use crate::checks::{Check, Context, PHANDLE_ARGS, Reporter};
+use crate::path::NodePath;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// A directory of bindings, usable as a check named `schema`.
+pub struct Schemas {
+    /// Each binding, by its path relative to the directory.
+    files: HashMap<PathBuf, Yaml>,
+    /// The bindings which apply to nodes with each compatible string.
+    compatible: HashMap<String, Vec<PathBuf>>,
+    /// Each key of a `patternProperties`, compiled if possible.
+    patterns: HashMap<String, Option<Regex>>,
+    /// A digest of the files, in hex.
+    digest: String,
+}
+
+/// A `$ref` may lead back to its own schema; give up beyond this depth.
+const MAX_DEPTH: usize = 32;
+
+impl Schemas {
+    /// Load every `.yaml` file beneath `dir`.
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let mut files = vec![];
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(d) = pending.pop() {
+            let entries = std::fs::read_dir(&d).map_err(|e| format!("{}: {e}", d.display()))?;
+            for entry in entries {
+                let path = entry.map_err(|e| format!("{}: {e}", d.display()))?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if path.extension().is_some_and(|e| e == "yaml") {
+                    let text = std::fs::read_to_string(&path)
+                        .map_err(|e| format!("{}: {e}", path.display()))?;
+                    files.push((path.strip_prefix(dir).unwrap().to_path_buf(), text));
+                }
+            }
+        }
+        Self::from_files(files)
+    }
+
+    /// Load bindings from the text of each file, given with its path relative to the directory.
+    pub fn from_files(files: impl IntoIterator<Item = (PathBuf, String)>) -> Result<Self, String> {
+        use sha2::{Digest, Sha256};
+        let mut files: Vec<(PathBuf, String)> = files.into_iter().collect();
+        files.sort();
+        let mut hasher = Sha256::new();
+        let mut schemas = Schemas {
+            files: HashMap::new(),
+            compatible: HashMap::new(),
+            patterns: HashMap::new(),
+            digest: String::new(),
+        };
+        for (path, text) in files {
+            for part in [path.to_string_lossy().as_bytes(), text.as_bytes()] {
+                hasher.update((part.len() as u64).to_le_bytes());
+                hasher.update(part);
+            }
+            let docs =
+                YamlLoader::load_from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+            let Some(doc) = docs.into_iter().next() else {
+                continue;
+            };
+            let mut strings = vec![];
+            if doc["select"] != Yaml::Boolean(false) {
+                mentioned_strings(&doc["properties"]["compatible"], &mut strings);
+            }
+            for s in strings {
+                let files = schemas.compatible.entry(s).or_default();
+                if !files.contains(&path) {
+                    files.push(path.clone());
+                }
+            }
+            compile_patterns(&doc, &mut schemas.patterns);
+            schemas.files.insert(path, doc);
+        }
+        schemas.digest = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        Ok(schemas)
+    }
+
+    /// Resolve `reference`, found in the file `from`.
+    fn resolve(&self, from: &Path, reference: &str) -> Target<'_> {
+        let (file, pointer) = reference.split_once('#').unwrap_or((reference, ""));
+        let file = if file.is_empty() {
+            from.to_path_buf()
+        } else if let Some(rest) = file
+            .strip_prefix("http://devicetree.org/schemas/")
+            .or_else(|| file.strip_prefix("/schemas/"))
+        {
+            PathBuf::from(rest)
+        } else {
+            normalize(&from.parent().unwrap_or(Path::new("")).join(file))
+        };
+        let Some((file, mut schema)) = self.files.get_key_value(&file) else {
+            return match (file.to_str(), pointer.strip_prefix("/definitions/")) {
+                (Some("types.yaml"), Some(name)) => Kind::from_type_name(name)
+                    .map(Target::Type)
+                    .unwrap_or(Target::Unknown),
+                _ => Target::Unknown,
+            };
+        };
+        for segment in pointer.split('/').filter(|s| !s.is_empty()) {
+            schema = match schema {
+                Yaml::Array(items) => {
+                    match segment.parse::<usize>().ok().and_then(|i| items.get(i)) {
+                        Some(item) => item,
+                        None => return Target::Unknown,
+                    }
+                }
+                _ => &schema[segment],
+            };
+        }
+        match schema {
+            Yaml::BadValue => Target::Unknown,
+            schema => Target::Schema(file, schema),
+        }
+    }
+
+    fn matches(&self, pattern: &str, name: &str) -> bool {
+        matches!(self.patterns.get(pattern), Some(Some(re)) if re.is_match(name))
+    }
+}
+
+enum Target<'s> {
+    Schema(&'s Path, &'s Yaml),
+    /// A property type of `types.yaml`.
+    Type(Kind),
+    Unknown,
+}
+
+/// Resolve `.` and `..` in a relative path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => _ = out.pop(),
+            Component::CurDir => (),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Collect the strings of each `const` and `enum` within `schema`.
+fn mentioned_strings(schema: &Yaml, out: &mut Vec<String>) {
+    match schema {
+        Yaml::Hash(hash) => {
+            for (key, value) in hash {
+                match (key.as_str(), value) {
+                    (Some("const"), Yaml::String(s)) => out.push(s.clone()),
+                    (Some("enum"), Yaml::Array(values)) => {
+                        out.extend(values.iter().filter_map(|v| v.as_str()).map(String::from))
+                    }
+                    _ => mentioned_strings(value, out),
+                }
+            }
+        }
+        Yaml::Array(items) => {
+            for item in items {
+                mentioned_strings(item, out);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn compile_patterns(schema: &Yaml, out: &mut HashMap<String, Option<Regex>>) {
+    match schema {
+        Yaml::Hash(hash) => {
+            if let Yaml::Hash(patterns) = &schema["patternProperties"] {
+                for pattern in patterns.keys().filter_map(|k| k.as_str()) {
+                    out.entry(pattern.into())
+                        .or_insert_with(|| Regex::new(pattern).ok());
+                }
+            }
+            for value in hash.values() {
+                compile_patterns(value, out);
+            }
+        }
+        Yaml::Array(items) => {
+            for item in items {
+                compile_patterns(item, out);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// The type of a property's value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    /// An empty property.
+    Flag,
+    String,
+    StringArray,
+    /// Big-endian integers of `bytes` bytes; exactly one, if `single`.
+    Int {
+        bytes: usize,
+        single: bool,
+    },
+}
+
+impl Kind {
+    /// Interpret a definition of `types.yaml`, such as `uint32-array`.
+    fn from_type_name(name: &str) -> Option<Kind> {
+        let (base, single) = match name.strip_suffix("-array") {
+            Some(base) => (base, false),
+            None => match name.strip_suffix("-matrix") {
+                Some(base) => (base, false),
+                None => (name, true),
+            },
+        };
+        let bits = base
+            .strip_prefix("uint")
+            .or_else(|| base.strip_prefix("int"));
+        Some(match (base, bits) {
+            ("flag", _) => Kind::Flag,
+            ("string", _) if single => Kind::String,
+            ("string" | "non-unique-string", _) => Kind::StringArray,
+            ("phandle", _) => Kind::Int { bytes: 4, single },
+            (_, Some(bits @ ("8" | "16" | "32" | "64"))) => Kind::Int {
+                bytes: bits.parse::<usize>().unwrap() / 8,
+                single,
+            },
+            _ => return None,
+        })
+    }
+
+    /// The type of properties defined by the specification, where a binding need not say.
+    fn of_standard_property(name: &str) -> Option<Kind> {
+        Some(match name {
+            "model" | "status" | "device_type" | "label" => Kind::String,
+            "compatible" => Kind::StringArray,
+            _ if name.ends_with("-names") => Kind::StringArray,
+            "reg" | "ranges" | "dma-ranges" | "interrupts" => Kind::Int {
+                bytes: 4,
+                single: false,
+            },
+            _ if name.starts_with('#') && name.ends_with("-cells") => Kind::Int {
+                bytes: 4,
+                single: true,
+            },
+            _ if PHANDLE_ARGS.iter().any(|p| p.matches(name)) => Kind::Int {
+                bytes: 4,
+                single: false,
+            },
+            _ => return None,
+        })
+    }
+
+    fn describe(self) -> String {
+        match self {
+            Kind::Flag => "an empty flag".into(),
+            Kind::String => "a string".into(),
+            Kind::StringArray => "a string list".into(),
+            Kind::Int {
+                bytes,
+                single: true,
+            } => format!("a single {}-bit integer", bytes * 8),
+            Kind::Int {
+                bytes,
+                single: false,
+            } => format!("a list of {}-bit integers", bytes * 8),
+        }
+    }
+
+    /// Split a value into items, or `None` if it does not have this type.
+    fn decode(self, value: &[u8]) -> Option<Vec<Item>> {
+        match self {
+            Kind::Flag => value.is_empty().then(Vec::new),
+            Kind::String | Kind::StringArray => {
+                let s = core::str::from_utf8(value.strip_suffix(&[0])?).ok()?;
+                let items: Vec<Item> = s.split('\0').map(|s| Item::Str(s.into())).collect();
+                (self == Kind::StringArray || items.len() == 1).then_some(items)
+            }
+            Kind::Int { bytes, single } => {
+                if value.is_empty() || !value.len().is_multiple_of(bytes) {
+                    return None;
+                }
+                if single && value.len() != bytes {
+                    return None;
+                }
+                let items = value
+                    .chunks_exact(bytes)
+                    .map(|chunk| Item::Int(chunk.iter().fold(0, |n, &b| (n << 8) | u64::from(b))));
+                Some(items.collect())
+            }
+        }
+    }
+}
+
+/// One element of a property's value, or of a `const` or `enum`.
+#[derive(Debug, PartialEq)]
+enum Item {
+    Str(String),
+    Int(u64),
+}
+
+impl Item {
+    fn from_yaml(y: &Yaml) -> Option<Item> {
+        match y {
+            Yaml::String(s) => Some(Item::Str(s.clone())),
+            Yaml::Integer(n) => Some(Item::Int(*n as u64)),
+            _ => None,
+        }
+    }
+}
+
+impl core::fmt::Display for Item {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Item::Str(s) => write!(f, "\"{s}\""),
+            Item::Int(n) => write!(f, "{n:#x}"),
+        }
+    }
+}
+
+/// A `type` as written in a binding, for messages.
+fn type_name(t: &Yaml) -> String {
+    match t {
+        Yaml::String(t) => format!("\"{t}\""),
+        _ => format!("{t:?}"),
+    }
+}
+
+/// The validation of one node against one binding.
+struct Validator<'v, 't, 'a> {
+    schemas: &'v Schemas,
+    ctx: &'v Context<'t, 'a>,
+    binding: &'v Path,
+}
+
+impl Validator<'_, '_, '_> {
+    fn node(
+        &self,
+        file: &Path,
+        schema: &Yaml,
+        path: &NodePath,
+        node: &BinaryNode,
+        depth: usize,
+        report: &mut Reporter,
+    ) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        if *schema == Yaml::Boolean(false) {
+            report.node(path, self.message("node is not permitted"));
+            return;
+        }
+        match &schema["type"] {
+            Yaml::BadValue => (),
+            Yaml::String(t) if t == "object" => (),
+            t => {
+                let message = format!("node should be a property of type {}", type_name(t));
+                report.node(path, self.message(message));
+                return;
+            }
+        }
+        if let Some(reference) = schema["$ref"].as_str()
+            && let Target::Schema(file, target) = self.schemas.resolve(file, reference)
+        {
+            self.node(file, target, path, node, depth + 1, report);
+        }
+        for sub in schema["allOf"].as_vec().into_iter().flatten() {
+            self.node(file, sub, path, node, depth + 1, report);
+        }
+        for name in schema["required"].as_vec().into_iter().flatten() {
+            let Some(name) = name.as_str() else {
+                continue;
+            };
+            if node.get_property(name).is_none() && node.get_child(name).is_none() {
+                let message = format!("required property \"{name}\" is missing");
+                report.node(path, self.message(message));
+            }
+        }
+        let mut subschemas: Vec<(&str, &Yaml)> = vec![];
+        if let Yaml::Hash(properties) = &schema["properties"] {
+            for (name, sub) in properties {
+                if let Some(name) = name.as_str() {
+                    subschemas.push((name, sub));
+                }
+            }
+        }
+        let patterns = match &schema["patternProperties"] {
+            Yaml::Hash(patterns) => patterns.iter().collect(),
+            _ => vec![],
+        };
+        let applicable = |name: &str| -> Vec<&Yaml> {
+            let named = subschemas.iter().filter(|(n, _)| *n == name);
+            let matched = patterns.iter().filter(|(pattern, _)| {
+                pattern
+                    .as_str()
+                    .is_some_and(|p| self.schemas.matches(p, name))
+            });
+            named
+                .map(|(_, sub)| *sub)
+                .chain(matched.map(|(_, sub)| *sub))
+                .collect()
+        };
+        for (name, value) in node.properties() {
+            for sub in applicable(name) {
+                self.property(file, sub, path, name, value, depth + 1, report);
+            }
+        }
+        for (name, child) in node.children() {
+            for sub in applicable(name) {
+                self.node(file, sub, &path.join(name), child, depth + 1, report);
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn property(
+        &self,
+        file: &Path,
+        schema: &Yaml,
+        path: &NodePath,
+        name: &str,
+        value: &[u8],
+        depth: usize,
+        report: &mut Reporter,
+    ) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        if *schema == Yaml::Boolean(false) {
+            report.property(path, name, self.message("property is not permitted"));
+            return;
+        }
+        let mut kind = match &schema["type"] {
+            Yaml::BadValue => None,
+            Yaml::String(t) if t == "boolean" => Some(Kind::Flag),
+            Yaml::String(t) if t == "string" => Some(Kind::String),
+            Yaml::String(t) if t == "integer" => Some(Kind::Int {
+                bytes: 4,
+                single: true,
+            }),
+            Yaml::String(t) if t == "array" => {
+                if value.is_empty() {
+                    report.property(path, name, self.message("property is not an array"));
+                    return;
+                }
+                None
+            }
+            Yaml::String(t) if t == "object" => {
+                report.property(path, name, self.message("property should be a node"));
+                return;
+            }
+            t => {
+                let message = format!("unsupported type {}", type_name(t));
+                report.property(path, name, self.message(message));
+                None
+            }
+        };
+        if let Some(reference) = schema["$ref"].as_str() {
+            match self.schemas.resolve(file, reference) {
+                Target::Schema(file, target) => {
+                    self.property(file, target, path, name, value, depth + 1, report)
+                }
+                Target::Type(k) => kind = Some(k),
+                Target::Unknown => (),
+            }
+        }
+        for sub in schema["allOf"].as_vec().into_iter().flatten() {
+            self.property(file, sub, path, name, value, depth + 1, report);
+        }
+        if let Some(kind) = kind
+            && kind.decode(value).is_none()
+        {
+            let message = format!("property is not {}", kind.describe());
+            report.property(path, name, self.message(message));
+            return;
+        }
+        let expected: Vec<Item> = match (&schema["const"], &schema["enum"]) {
+            (Yaml::BadValue, Yaml::Array(values)) => {
+                values.iter().filter_map(Item::from_yaml).collect()
+            }
+            (value, _) => Item::from_yaml(value).into_iter().collect(),
+        };
+        // Absent a declared type, interpret the value like the expected values.
+        let kind = kind
+            .or_else(|| Kind::of_standard_property(name))
+            .or(match expected.first() {
+                Some(Item::Str(_)) => Some(Kind::StringArray),
+                Some(Item::Int(_)) => Some(Kind::Int {
+                    bytes: 4,
+                    single: false,
+                }),
+                None => None,
+            });
+        let items = kind.and_then(|k| k.decode(value));
+        if !expected.is_empty()
+            && let Some(items) = &items
+            && !matches!(&items[..], [item] if expected.contains(item))
+        {
+            let list: Vec<String> = expected.iter().map(|e| e.to_string()).collect();
+            let expected = match schema["const"] {
+                Yaml::BadValue => format!("one of {}", list.join(", ")),
+                _ => list.join(""),
+            };
+            let message = match items.len() {
+                1 => format!("value should be {expected}"),
+                n => format!("property has {n} items, but should be {expected}"),
+            };
+            report.property(path, name, self.message(message));
+        }
+        let (min, max) = (schema["minItems"].as_i64(), schema["maxItems"].as_i64());
+        if min.is_none() && max.is_none() {
+            return;
+        }
+        let Some(n) = self.count(path, name, value, kind, items) else {
+            return;
+        };
+        if let Some(min) = min
+            && (n as i64) < min
+        {
+            let message = format!("property has {n} items; at least {min} are required");
+            report.property(path, name, self.message(message));
+        }
+        if let Some(max) = max
+            && (n as i64) > max
+        {
+            let message = format!("property has {n} items; at most {max} are permitted");
+            report.property(path, name, self.message(message));
+        }
+    }
+
+    /// The number of items in a value, as counted by `minItems` and `maxItems`.
+    fn count(
+        &self,
+        path: &NodePath,
+        name: &str,
+        value: &[u8],
+        kind: Option<Kind>,
+        items: Option<Vec<Item>>,
+    ) -> Option<usize> {
+        let ctx = self.ctx;
+        let node = ctx.tree.walk(path.segments())?;
+        let cells_per_entry = match name {
+            "reg" => {
+                let parent = ctx.parent(path)?;
+                let (ac, sc) = (parent.address_cells(), parent.size_cells());
+                // An invalid cell count is reported by reg_format.
+                Some((ac as usize).checked_add(sc as usize)?)
+            }
+            "interrupts" => {
+                let (_, parent) = ctx.interrupt_parent(path).ok()?;
+                parent
+                    .get_u32("#interrupt-cells")
+                    .map(|cells| cells as usize)
+            }
+            _ => None,
+        };
+        if let Some(cells) = cells_per_entry {
+            return (cells != 0).then(|| value.len() / 4 / cells);
+        }
+        if let Some(phandle_args) = PHANDLE_ARGS.iter().find(|p| p.matches(name)) {
+            let list = node.get_cells(name)?;
+            let specifiers = ctx
+                .phandle_args(&list, phandle_args.cells, phandle_args.default_cells)
+                .ok()?;
+            return Some(specifiers.len());
+        }
+        match kind {
+            Some(_) => items.map(|items| items.len()),
+            // Guess:  text, or else cells.
+            None => Kind::StringArray
+                .decode(value)
+                .filter(|items| {
+                    items
+                        .iter()
+                        .all(|i| matches!(i, Item::Str(s) if !s.is_empty()))
+                })
+                .or_else(|| {
+                    Kind::Int {
+                        bytes: 4,
+                        single: false,
+                    }
+                    .decode(value)
+                })
+                .map(|items| items.len()),
+        }
+    }
+
+    fn message(&self, message: impl core::fmt::Display) -> String {
+        format!("{message} (per {})", self.binding.display())
+    }
+}
+
+impl Check for Schemas {
+    fn name(&self) -> &'static str {
+        "schema"
+    }
+
+    fn fingerprint(&self) -> String {
+        self.digest.clone()
+    }
+
+    fn check(&self, ctx: &Context, report: &mut Reporter) {
+        for (path, node) in ctx.nodes() {
+            let Some(compatible) = node.get_strings("compatible") else {
+                continue;
+            };
+            let mut bindings: Vec<&PathBuf> = vec![];
+            for c in compatible {
+                for binding in self.compatible.get(c).into_iter().flatten() {
+                    if !bindings.contains(&binding) {
+                        bindings.push(binding);
+                    }
+                }
+            }
+            for binding in bindings {
+                let validator = Validator {
+                    schemas: self,
+                    ctx,
+                    binding,
+                };
+                validator.node(binding, &self.files[binding], &path, node, 0, report);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_schema() {
+    let binding = r#"
+$id: http://devicetree.org/schemas/serial/acme,uart.yaml#
+$schema: http://devicetree.org/meta-schemas/core.yaml#
+title: ACME UART
+properties:
+  compatible:
+    enum:
+      - acme,uart
+      - acme,uart2
+  reg:
+    maxItems: 1
+  clocks:
+    minItems: 1
+  clock-names:
+    const: baud
+  current-speed:
+    $ref: /schemas/types.yaml#/definitions/uint32
+  acme,fifo-size:
+    enum: [16, 64]
+  acme,loopback:
+    type: boolean
+  acme,legacy: false
+  acme,label:
+    type: string
+  acme,id:
+    type: integer
+  acme,lanes:
+    type: array
+  acme,ratio:
+    type: number
+  acme,bridge:
+    type: object
+  acme,mode:
+    type: string
+patternProperties:
+  "^port@[0-9]$":
+    $ref: "../graph.yaml#/definitions/port"
+required:
+  - compatible
+  - reg
+  - clocks
+"#;
+    let graph = r#"
+definitions:
+  port:
+    required:
+      - reg
+"#;
+    let schemas = Schemas::from_files([
+        (PathBuf::from("serial/acme,uart.yaml"), binding.into()),
+        (PathBuf::from("graph.yaml"), graph.into()),
+    ])
+    .unwrap();
+    let source = r#"
+/dts-v1/;
+/ {
+    #address-cells = <1>;
+    #size-cells = <1>;
+    osc: osc { #clock-cells = <0>; };
+    serial@1000 {
+        compatible = "acme,uart";
+        reg = <0x1000 0x100>;
+        clocks = <&osc>;
+        clock-names = "baud";
+        current-speed = <115200>;
+        acme,fifo-size = <16>;
+        acme,label = "console";
+        acme,id = <3>;
+        acme,lanes = <1 2>;
+    };
+    serial@2000 {
+        compatible = "acme,uart2", "acme,uart";
+        reg = <0x2000 0x100>, <0x3000 0x100>;
+        clock-names = "bus";
+        current-speed = /bits/ 64 <115200>;
+        acme,fifo-size = <32>;
+        acme,loopback = <1>;
+        acme,legacy;
+        acme,label = "a", "b";
+        acme,id = <3 4>;
+        acme,lanes;
+        acme,ratio = <2>;
+        acme,bridge = <1>;
+        acme,mode { };
+        port@0 { };
+    };
+    serial@4000 { compatible = "other,uart"; };
+};
+"#;
+    let mut registry = crate::checks::Registry::empty();
+    registry.register(Box::new(schemas));
+    let loader = crate::fs::DummyLoader;
+    let arena = crate::Arena::new();
+    let dts = crate::parse::parse_typed(source, &arena).unwrap();
+    let mut scribe = crate::error::Scribe::new(false);
+    crate::compile_parsed(&loader, &arena, dts, Some(&registry), None, &mut scribe);
+    let (warnings, errors) = scribe.into_inner();
+    assert!(errors.is_empty());
+    let messages: Vec<String> = warnings
+        .iter()
+        .map(|w| {
+            let pest::error::ErrorVariant::CustomError { message } = &w.pest_error.variant else {
+                unreachable!()
+            };
+            message.replace(" (per serial/acme,uart.yaml)", "")
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "(schema) /serial@2000: required property \"clocks\" is missing",
+            "(schema) /serial@2000/compatible: property has 2 items, but should be one of \
+             \"acme,uart\", \"acme,uart2\"",
+            "(schema) /serial@2000/reg: property has 2 items; at most 1 are permitted",
+            "(schema) /serial@2000/clock-names: value should be \"baud\"",
+            "(schema) /serial@2000/current-speed: property is not a single 32-bit integer",
+            "(schema) /serial@2000/acme,fifo-size: value should be one of 0x10, 0x40",
+            "(schema) /serial@2000/acme,loopback: property is not an empty flag",
+            "(schema) /serial@2000/acme,legacy: property is not permitted",
+            "(schema) /serial@2000/acme,label: property is not a string",
+            "(schema) /serial@2000/acme,id: property is not a single 32-bit integer",
+            "(schema) /serial@2000/acme,lanes: property is not an array",
+            "(schema) /serial@2000/acme,ratio: unsupported type \"number\"",
+            "(schema) /serial@2000/acme,bridge: property should be a node",
+            "(schema) /serial@2000/acme,mode: node should be a property of type \"string\"",
+            "(schema) /serial@2000/port@0: required property \"reg\" is missing",
+        ]
+    );
+}